@@ -1,6 +1,10 @@
 use proc_macro2::Span;
 use quote::quote;
-use syn::{parse_macro_input, Data, DeriveInput, Lit, Meta, NestedMeta};
+use syn::parse::{Parse, ParseStream};
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, FnArg, Fields, Ident, ItemFn, Lit, LitInt,
+    Meta, NestedMeta, Pat, Token,
+};
 
 #[proc_macro_derive(JyMenu, attributes(i18n))]
 pub fn menu(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -120,3 +124,264 @@ pub fn menu(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     };
     default.into()
 }
+
+// the value of `key` in `#[jy(key = N)]`
+fn jy_attr(attrs: &[Attribute], key: &str) -> syn::Result<Option<usize>> {
+    for attr in attrs.iter().filter(|v| v.path.is_ident("jy")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(v) => v,
+            v => return Err(syn::Error::new_spanned(v, "expected #[jy(key = N)]")),
+        };
+        for nested in list.nested.iter() {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident(key) => {
+                    if let Lit::Int(lit) = &nv.lit {
+                        return Ok(Some(lit.base10_parse::<usize>()?));
+                    }
+                    return Err(syn::Error::new_spanned(&nv.lit, "expected an integer"));
+                }
+                NestedMeta::Meta(Meta::NameValue(nv))
+                    if nv.path.is_ident("offset") || nv.path.is_ident("size") => {}
+                v => return Err(syn::Error::new_spanned(v, "unknown jy attribute")),
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[proc_macro_derive(JyRecord, attributes(jy))]
+pub fn record(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    match record_impl(&ast) {
+        Ok(v) => v.into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+fn record_impl(ast: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &ast.data {
+        Data::Struct(v) => match &v.fields {
+            Fields::Named(v) => &v.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ast,
+                    "Only structs with named fields are supported",
+                ))
+            }
+        },
+        _ => return Err(syn::Error::new_spanned(ast, "Only structs are supported")),
+    };
+    if !ast.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &ast.generics,
+            "Generic records are not supported",
+        ));
+    }
+
+    let struct_name = &ast.ident;
+    let idents = fields
+        .iter()
+        .map(|v| v.ident.as_ref().unwrap())
+        .collect::<Vec<_>>();
+    let tys = fields.iter().map(|v| &v.ty).collect::<Vec<_>>();
+    let names = idents.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    let what = struct_name.to_string();
+
+    // the offset of a field is the sum of the sizes before it
+    let offsets = (0..tys.len())
+        .map(|i| {
+            let before = &tys[..i];
+            quote! { 0 #(+ <#before as crate::game::RecordField>::SIZE)* }
+        })
+        .collect::<Vec<_>>();
+
+    let mut checks = vec![];
+    for (i, field) in fields.iter().enumerate() {
+        if let Some(expected) = jy_attr(&field.attrs, "offset")? {
+            let offset = &offsets[i];
+            let msg = format!(
+                "{}::{} is not at offset {}",
+                struct_name, names[i], expected
+            );
+            checks.push(quote! {
+                const _: () = assert!(#offset == #expected, #msg);
+            });
+        }
+    }
+    if let Some(expected) = jy_attr(&ast.attrs, "size")? {
+        let msg = format!("{} is not {} bytes", struct_name, expected);
+        checks.push(quote! {
+            const _: () = assert!(
+                <#struct_name as crate::game::Record>::SIZE == #expected,
+                #msg
+            );
+        });
+    }
+
+    Ok(quote! {
+        impl crate::game::Record for #struct_name {
+            const SIZE: usize = 0 #(+ <#tys as crate::game::RecordField>::SIZE)*;
+            const OFFSETS: &'static [(&'static str, usize)] = &[#((#names, #offsets)),*];
+
+            fn from_bytes(data: &[u8]) -> Result<Self, crate::game::JyDataError> {
+                let size = <Self as crate::game::Record>::SIZE;
+                if data.len() < size {
+                    return Err(crate::game::JyDataError::Truncated {
+                        what: #what,
+                        need: size,
+                        got: data.len(),
+                    });
+                }
+                let mut c = std::io::Cursor::new(data);
+                Ok(#struct_name {
+                    #(#idents: <#tys as crate::game::RecordField>::read(&mut c),)*
+                })
+            }
+
+            fn to_bytes(&self) -> Vec<u8> {
+                let mut out = Vec::with_capacity(<Self as crate::game::Record>::SIZE);
+                #(crate::game::RecordField::write(&self.#idents, &mut out);)*
+                out
+            }
+        }
+
+        #(#checks)*
+    })
+}
+
+// `#[jy_instruct(N)]`, `#[jy_instruct(N, handler = path)]`, `#[jy_instruct(N, yields)]`
+struct InstructArgs {
+    id: i32,
+    handler: Option<syn::Path>,
+    yields: bool,
+}
+
+impl Parse for InstructArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let id = input.parse::<LitInt>()?.base10_parse::<i32>()?;
+        let mut args = InstructArgs {
+            id,
+            handler: None,
+            yields: false,
+        };
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            let key = input.parse::<Ident>()?;
+            if key == "handler" {
+                input.parse::<Token![=]>()?;
+                args.handler = Some(input.parse()?);
+            } else if key == "yields" {
+                args.yields = true;
+            } else {
+                return Err(syn::Error::new_spanned(key, "unknown jy_instruct argument"));
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Declares a script instruction from its constructor `fn(args) -> JyEvent`,
+/// generates the `InstructN` binding that converts the lua arguments and schedules the handler.
+#[proc_macro_attribute]
+pub fn jy_instruct(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let args = parse_macro_input!(attr as InstructArgs);
+    let func = parse_macro_input!(item as ItemFn);
+    match instruct_impl(&args, &func) {
+        Ok(v) => v.into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+fn instruct_impl(args: &InstructArgs, func: &ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let fn_name = &func.sig.ident;
+    let expected = format!("instruct_{}", args.id);
+    if fn_name != &expected {
+        return Err(syn::Error::new_spanned(
+            fn_name,
+            format!("expected the function to be named {}", expected),
+        ));
+    }
+
+    let mut idents = vec![];
+    let mut tys = vec![];
+    for input in func.sig.inputs.iter() {
+        let typed = match input {
+            FnArg::Typed(v) => v,
+            FnArg::Receiver(v) => {
+                return Err(syn::Error::new_spanned(v, "instructions take no self"))
+            }
+        };
+        match &*typed.pat {
+            Pat::Ident(v) => idents.push(v.ident.clone()),
+            v => return Err(syn::Error::new_spanned(v, "expected a plain argument name")),
+        }
+        tys.push(&*typed.ty);
+    }
+    let names = idents.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    let indices = 1..=idents.len();
+    let words = 0..idents.len();
+    let arity = idents.len();
+
+    let id = args.id;
+    let yields = args.yields;
+    let struct_name = Ident::new(&format!("Instruct{}", id), fn_name.span());
+    let add_handler = match &args.handler {
+        Some(handler) => quote! {
+            use bevy::prelude::*;
+            set.with_system(#handler.after("dispatch").label("execute"))
+        },
+        None => quote! { set },
+    };
+
+    Ok(quote! {
+        #func
+
+        pub struct #struct_name;
+
+        impl crate::game::instructs::Instruct for #struct_name {
+            const ID: i32 = #id;
+            const YIELDS: bool = #yields;
+            const ARITY: usize = #arity;
+
+            fn bind<'lua>(
+                lua_ctx: rlua::Context<'lua>,
+                que: crate::game::instructs::EventQueue,
+            ) -> rlua::Result<rlua::Function<'lua>> {
+                lua_ctx.create_function(move |lua_ctx, args: rlua::MultiValue| {
+                    // more arguments are the jump offsets the converted kdef still passes
+                    if args.len() < #arity {
+                        return Err(rlua::Error::RuntimeError(format!(
+                            "{} takes {} arguments, got {}",
+                            #expected,
+                            #arity,
+                            args.len()
+                        )));
+                    }
+                    let mut args = args.into_iter();
+                    #(
+                        let #idents: #tys = rlua::FromLua::from_lua(args.next().unwrap(), lua_ctx)
+                            .map_err(|e| {
+                                rlua::Error::RuntimeError(format!(
+                                    "{} argument {} `{}`: {}",
+                                    #expected, #indices, #names, e
+                                ))
+                            })?;
+                    )*
+                    que.lock().unwrap().push(#fn_name(#(#idents),*));
+                    Ok(true)
+                })
+            }
+
+            fn from_kdef(args: &[i16]) -> crate::game::instructs::JyEvent {
+                #fn_name(#(<#tys as From<i16>>::from(args[#words])),*)
+            }
+
+            fn add_handler(set: bevy::prelude::SystemSet) -> bevy::prelude::SystemSet {
+                #add_handler
+            }
+        }
+    })
+}
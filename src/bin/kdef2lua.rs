@@ -0,0 +1,93 @@
+//! Decompiles kdef.grp into the `oldevent_N.lua` scripts of assets/script.
+//!
+//! kdef2lua [data dir] [script dir] [--encoding big5|gbk|utf-8]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+
+use jy::game::encoding::TextEncoding;
+use jy::game::kdef::{self, KdefNames};
+use jy::game::save::{read_grp, SAVE_DIR};
+use jy::game::structs::{Kdef, Person, Scene, Talks, Thing};
+use jy::game::GrpAsset;
+use jy::prelude::Settings;
+
+const SCRIPT_DIR: &str = "assets/script";
+
+fn read(dir: &Path, name: &str) -> Result<GrpAsset> {
+    read_grp(dir, name).with_context(|| format!("reading {}.grp in {}", name, dir.display()))
+}
+
+fn names(talks: &Talks, ranger: &GrpAsset, encoding: TextEncoding) -> Result<KdefNames> {
+    let mut names = KdefNames::default();
+    for i in 0..talks.0.idx.len().saturating_sub(1) {
+        // the scripts keep the line breaks of talk.grp
+        names
+            .talks
+            .insert(i as i16, talks.get(i, encoding)?.replace('\n', "*"));
+    }
+    for person in ranger.records::<Person>(1)? {
+        names
+            .people
+            .entry(person.avatar())
+            .or_insert_with(|| person.name(encoding));
+    }
+    for (i, thing) in ranger.records::<Thing>(2)?.iter().enumerate() {
+        names.things.insert(i as i16, thing.name(encoding));
+    }
+    for (i, scene) in ranger.records::<Scene>(3)?.iter().enumerate() {
+        names.scenes.insert(i as i16, scene.name(encoding));
+    }
+    Ok(names)
+}
+
+fn main() -> Result<()> {
+    let mut dirs = vec![];
+    let mut text_encoding = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--encoding" => {
+                let v = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--encoding needs a value"))?;
+                text_encoding = Some(TextEncoding::from_str(&v).map_err(|e| anyhow!(e))?);
+            }
+            _ => dirs.push(PathBuf::from(arg)),
+        }
+    }
+    let mut dirs = dirs.into_iter();
+    let data_dir = dirs.next().unwrap_or_else(|| SAVE_DIR.into());
+    let script_dir = dirs.next().unwrap_or_else(|| SCRIPT_DIR.into());
+    let text_encoding = match text_encoding {
+        Some(v) => v,
+        None => Settings::load().map_or(TextEncoding::Big5, |v| v.encoding()),
+    };
+
+    let kdef = Kdef(read(&data_dir, "kdef")?);
+    let talks = Talks(read(&data_dir, "talk")?);
+    let names = names(&talks, &read(&data_dir, "ranger")?, text_encoding)?;
+
+    fs::create_dir_all(&script_dir)?;
+    let mut written = 0;
+    for event_id in 0..kdef.0.idx.len().saturating_sub(1) {
+        let code = kdef.get(event_id)?;
+        // an empty event is a lone end marker
+        if code.iter().all(|v| *v == -1) {
+            continue;
+        }
+        match kdef::to_lua(event_id, &code, &names) {
+            Ok(lua) => {
+                fs::write(script_dir.join(format!("oldevent_{}.lua", event_id)), lua)?;
+                written += 1;
+            }
+            Err(e) => eprintln!("event {}: {}", event_id, e),
+        }
+    }
+    println!("{} scripts written to {}", written, script_dir.display());
+    Ok(())
+}
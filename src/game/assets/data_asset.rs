@@ -24,7 +24,7 @@ impl AssetLoader for DataAssetLoader {
     }
 
     fn extensions(&self) -> &[&str] {
-        &["col", "002"]
+        &["col", "002", "sta"]
     }
 }
 
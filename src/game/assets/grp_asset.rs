@@ -6,6 +6,8 @@ use bevy::log::debug;
 use bevy::reflect::TypeUuid;
 use bevy::utils::BoxedFuture;
 
+use crate::game::{records, JyDataError, Record};
+
 /// A source of audio data
 #[derive(Debug, Clone, TypeUuid)]
 #[uuid = "7a14806a-672b-443b-8d16-4f18afefa464"]
@@ -15,14 +17,30 @@ pub struct GrpAsset {
 }
 
 impl GrpAsset {
-    pub fn idx(&self, i: usize) -> Option<&[u8]> {
-        let next = *self.idx.get(i + 1).unwrap_or(&0);
-        let cur = *self.idx.get(i).unwrap_or(&0);
-        if next - cur <= 0 {
-            None
-        } else {
-            Some(&self.data[cur..next])
+    pub fn idx(&self, i: usize) -> Result<&[u8], JyDataError> {
+        let (cur, next) = match (self.idx.get(i), self.idx.get(i + 1)) {
+            (Some(cur), Some(next)) => (*cur, *next),
+            _ => {
+                return Err(JyDataError::NoEntry {
+                    entry: i,
+                    len: self.idx.len().saturating_sub(1),
+                })
+            }
+        };
+        if next < cur || next > self.data.len() {
+            return Err(JyDataError::BadEntry {
+                entry: i,
+                start: cur,
+                end: next,
+                len: self.data.len(),
+            });
         }
+        Ok(&self.data[cur..next])
+    }
+
+    /// The records of entry `i`.
+    pub fn records<T: Record>(&self, i: usize) -> Result<Vec<T>, JyDataError> {
+        records(self.idx(i)?)
     }
 }
 
@@ -65,7 +83,9 @@ async fn load_grp<'a, 'b>(
         }
         Err(err) => {
             if let AssetIoError::NotFound(p) = err {
-                println!("not exists...{}", p.to_str().unwrap());
+                // allsin / alldef and the save slots have no idx, the grp is one entry then
+                debug!("no idx {}, read {} as a whole", p.display(), path);
+                idx.push(bytes.len());
             } else {
                 return Err(err.into());
             }
@@ -109,6 +129,18 @@ pub mod test {
         })
     }
     
+    #[test]
+    fn bad_idx() {
+        let gs = GrpAsset {
+            idx: vec![0, 4, 2, 10],
+            data: vec![0; 8],
+        };
+        assert_eq!(gs.idx(0), Ok(&[0u8; 4][..]));
+        assert!(matches!(gs.idx(1), Err(crate::game::JyDataError::BadEntry { .. })));
+        assert!(matches!(gs.idx(2), Err(crate::game::JyDataError::BadEntry { .. })));
+        assert!(matches!(gs.idx(3), Err(crate::game::JyDataError::NoEntry { .. })));
+    }
+
     #[test]
     fn test_asset() {
         let gs = load_gs("./assets/org/data/smap.grp".into(), "./assets/org/data/smap.idx".into());
@@ -0,0 +1,34 @@
+use anyhow::Result;
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+
+/// The source of an event script
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "7a14806a-672b-443b-8d16-4f18afefa466"]
+pub struct LuaScript {
+    pub source: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct LuaScriptLoader;
+
+impl AssetLoader for LuaScriptLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(LuaScript {
+                source: bytes.into(),
+            }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["lua"]
+    }
+}
@@ -1,5 +1,7 @@
 pub use data_asset::*;
 pub use grp_asset::*;
+pub use lua_asset::*;
 
 mod data_asset;
 mod grp_asset;
+mod lua_asset;
@@ -0,0 +1,416 @@
+use std::cmp::Reverse;
+use std::str::FromStr;
+
+use crate::game::combat::{expected, Hit};
+use crate::game::structs::{Thing, Wugong};
+
+use super::field::{aims, area, distance, Field};
+
+/// What an enemy does with its turn, after walking to `to`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub to: (usize, usize),
+    pub act: Act,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Act {
+    // aims the skill of this index in `Unit::skills` at the tile
+    Attack((usize, usize), usize),
+    // takes the carried thing of this index in `Unit::items`
+    Item(usize),
+    Wait,
+}
+
+/// How the enemies play their turns, one per difficulty.
+pub trait Brain: Send + Sync {
+    fn plan(&self, field: &Field, unit: usize, wugongs: &[Wugong], things: &[Thing]) -> Plan;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("unknown difficulty {}", s)),
+        }
+    }
+}
+
+impl Difficulty {
+    pub fn brain(&self) -> Box<dyn Brain> {
+        match self {
+            Difficulty::Easy => Box::new(Reckless),
+            Difficulty::Normal => Box::new(Tactics {
+                heal_below: 30,
+                detox_at: 60,
+                retreat_at: 80,
+            }),
+            Difficulty::Hard => Box::new(Tactics {
+                heal_below: 50,
+                detox_at: 30,
+                retreat_at: 60,
+            }),
+        }
+    }
+}
+
+// whether the hits kill someone and the life and 内力 they take, the life a unit has left
+// is all it can lose
+fn weigh(field: &Field, hits: &[(usize, Hit)]) -> (bool, i32) {
+    let kills = hits
+        .iter()
+        .any(|(t, hit)| hit.hurt >= field.units[*t].stats.life);
+    let hurt = hits
+        .iter()
+        .map(|(t, hit)| (hit.hurt.min(field.units[*t].stats.life) + hit.neili_loss) as i32)
+        .sum();
+    (kills, hurt)
+}
+
+// the expected hits of a wugong at `pos` aimed at `aim` on the opponents in its area
+fn hits_at(
+    field: &Field,
+    unit: usize,
+    (wugong, level): (&Wugong, usize),
+    pos: (usize, usize),
+    aim: (usize, usize),
+) -> Vec<(usize, Hit)> {
+    let me = &field.units[unit].stats;
+    field
+        .targets_on(unit, &area(wugong, level, pos, aim))
+        .into_iter()
+        .map(|t| {
+            let them = &field.units[t];
+            let d = distance(pos, them.pos);
+            (t, expected(me, &them.stats, wugong, level, d))
+        })
+        .collect()
+}
+
+// every aim of every skill of the unit at `pos` that hits someone, as (skill, aim, hits)
+fn strikes_from<'a>(
+    field: &'a Field,
+    unit: usize,
+    pos: (usize, usize),
+    wugongs: &'a [Wugong],
+) -> impl Iterator<Item = (usize, (usize, usize), Vec<(usize, Hit)>)> + 'a {
+    (0..field.units[unit].skills.len())
+        .filter_map(move |skill| Some((skill, field.skill(unit, skill, wugongs)?)))
+        .flat_map(move |(skill, w)| {
+            aims(w.0, w.1, pos)
+                .into_iter()
+                .map(move |aim| (skill, aim, hits_at(field, unit, w, pos, aim)))
+        })
+        .filter(|v| !v.2.is_empty())
+}
+
+/// The skill with the most expected hurt aimed at `aim` from `pos`, the cheaper one on a tie,
+/// with its expected hits. Only the skills aimed that far and hitting someone count.
+pub fn best_skill(
+    field: &Field,
+    unit: usize,
+    pos: (usize, usize),
+    aim: (usize, usize),
+    wugongs: &[Wugong],
+) -> Option<(usize, Vec<(usize, Hit)>)> {
+    (0..field.units[unit].skills.len())
+        .filter_map(|skill| {
+            let w = field.skill(unit, skill, wugongs)?;
+            if !aims(w.0, w.1, pos).contains(&aim) {
+                return None;
+            }
+            let hits = hits_at(field, unit, w, pos, aim);
+            if hits.is_empty() {
+                None
+            } else {
+                Some((skill, hits))
+            }
+        })
+        .max_by_key(|(skill, hits)| {
+            (
+                weigh(field, hits).1,
+                Reverse(hits[0].1.neili_cost),
+                Reverse(*skill),
+            )
+        })
+}
+
+/// Walks to the nearest ally and aims its first wugong at the weakest one it reaches.
+pub struct Reckless;
+
+impl Brain for Reckless {
+    fn plan(&self, field: &Field, unit: usize, wugongs: &[Wugong], _: &[Thing]) -> Plan {
+        let to = field.approach(unit);
+        let act = strikes_from(field, unit, to, wugongs)
+            .filter(|(skill, ..)| *skill == 0)
+            .min_by_key(|(_, aim, hits)| {
+                let weakest = hits.iter().map(|(t, _)| field.units[*t].stats.life).min();
+                (weakest, *aim)
+            })
+            .map_or(Act::Wait, |(skill, aim, _)| Act::Attack(aim, skill));
+        Plan { to, act }
+    }
+}
+
+/// Takes its medicine when low, backs off when badly wounded and otherwise walks to the
+/// hit of the most expected hurt in reach of any of its wugongs, a kill first.
+pub struct Tactics {
+    // the percent of its 生命 it heals below
+    pub heal_below: i16,
+    // the 中毒程度 it takes an antidote at
+    pub detox_at: i16,
+    // the 受伤程度 it backs off at
+    pub retreat_at: i16,
+}
+
+impl Tactics {
+    // the carried thing that does the most of `what`
+    fn item(
+        field: &Field,
+        unit: usize,
+        things: &[Thing],
+        what: fn(&Thing) -> i16,
+    ) -> Option<usize> {
+        field.units[unit]
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (id, _))| things.get(*id).map(|v| (i, what(v))))
+            .filter(|(_, v)| *v > 0)
+            .max_by_key(|(i, v)| (*v, Reverse(*i)))
+            .map(|v| v.0)
+    }
+
+    // the reachable tile the opponents take the longest to walk to
+    fn retreat(field: &Field, unit: usize) -> (usize, usize) {
+        let walks = field.distances(unit);
+        field
+            .reachable(unit)
+            .into_iter()
+            .max_by_key(|(pos, steps)| {
+                let walk = walks.get(pos).copied().unwrap_or(usize::MAX);
+                (walk, Reverse(*steps), Reverse(*pos))
+            })
+            .map_or(field.units[unit].pos, |v| v.0)
+    }
+
+    // the best hit in reach, every wugong aimed from every tile it walks to
+    fn attack(field: &Field, unit: usize, wugongs: &[Wugong]) -> Option<Plan> {
+        let tiles = field.reachable(unit);
+        tiles
+            .iter()
+            .flat_map(|(pos, steps)| {
+                strikes_from(field, unit, *pos, wugongs)
+                    .map(move |(skill, aim, hits)| (*pos, *steps, skill, aim, hits))
+            })
+            .map(|(pos, steps, skill, aim, hits)| {
+                let (kills, hurt) = weigh(field, &hits);
+                let key = (
+                    kills,
+                    hurt,
+                    Reverse(hits[0].1.neili_cost),
+                    Reverse(steps),
+                    Reverse((pos, aim, skill)),
+                );
+                (key, pos, aim, skill)
+            })
+            .max_by_key(|v| v.0)
+            .map(|(_, to, aim, skill)| Plan {
+                to,
+                act: Act::Attack(aim, skill),
+            })
+    }
+}
+
+impl Brain for Tactics {
+    fn plan(&self, field: &Field, unit: usize, wugongs: &[Wugong], things: &[Thing]) -> Plan {
+        let me = &field.units[unit].stats;
+        let wounded = me.injure >= self.retreat_at;
+        let stay = || {
+            if wounded {
+                Tactics::retreat(field, unit)
+            } else {
+                field.units[unit].pos
+            }
+        };
+        if (me.life as i32) * 100 < (me.life_max as i32) * self.heal_below as i32 {
+            if let Some(i) = Tactics::item(field, unit, things, Thing::add_life) {
+                return Plan {
+                    to: stay(),
+                    act: Act::Item(i),
+                };
+            }
+        }
+        if me.tox >= self.detox_at {
+            if let Some(i) = Tactics::item(field, unit, things, |v| -v.add_detox()) {
+                return Plan {
+                    to: stay(),
+                    act: Act::Item(i),
+                };
+            }
+        }
+        if wounded {
+            return Plan {
+                to: stay(),
+                act: Act::Wait,
+            };
+        }
+        Tactics::attack(field, unit, wugongs).unwrap_or_else(|| Plan {
+            to: field.approach(unit),
+            act: Act::Wait,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::battle::field::fixture::{open_field, plain, ranged, unit};
+    use crate::game::battle::field::Side;
+    use crate::game::record;
+
+    fn tactics() -> Tactics {
+        Tactics {
+            heal_below: 30,
+            detox_at: 50,
+            retreat_at: 60,
+        }
+    }
+
+    #[test]
+    fn picks_the_wugong() {
+        let field = open_field(vec![
+            unit(Side::Enemy, (10, 10), 100, 45),
+            unit(Side::Ally, (11, 10), 100, 45),
+        ]);
+        // the stronger one
+        let wugongs = [plain(100, 10), plain(400, 10)];
+        let best = |wugongs: &[Wugong]| best_skill(&field, 0, (10, 10), (11, 10), wugongs);
+        assert_eq!(best(&wugongs).unwrap().0, 1);
+        // as strong, the cheaper one
+        let wugongs = [plain(400, 10), plain(400, 40)];
+        assert_eq!(best(&wugongs).unwrap().0, 0);
+        assert_eq!(best(&[]), None);
+    }
+
+    #[test]
+    fn goes_for_the_kill() {
+        let field = open_field(vec![
+            unit(Side::Enemy, (10, 10), 100, 45),
+            unit(Side::Ally, (13, 10), 100, 45),
+            unit(Side::Ally, (10, 13), 5, 45),
+            unit(Side::Ally, (30, 30), 100, 45),
+        ]);
+        let wugongs = [plain(100, 10), plain(300, 10)];
+        // either wugong kills it, the first one does
+        let plan = tactics().plan(&field, 0, &wugongs, &[]);
+        assert_eq!(
+            plan,
+            Plan {
+                to: (10, 12),
+                act: Act::Attack((10, 13), 0)
+            }
+        );
+
+        // out of reach it walks closer
+        let far = open_field(vec![
+            unit(Side::Enemy, (10, 10), 100, 45),
+            unit(Side::Ally, (20, 10), 100, 45),
+        ]);
+        let plan = tactics().plan(&far, 0, &wugongs, &[]);
+        assert_eq!(
+            plan,
+            Plan {
+                to: (13, 10),
+                act: Act::Wait
+            }
+        );
+        assert_eq!(Reckless.plan(&far, 0, &wugongs, &[]), plan);
+    }
+
+    #[test]
+    fn aims_by_reach() {
+        let field = open_field(vec![
+            unit(Side::Enemy, (10, 10), 100, 45),
+            unit(Side::Ally, (20, 10), 100, 45),
+        ]);
+        // only the weaker one reaches, from as close as it gets
+        let wugongs = [plain(400, 10), ranged(100, 8)];
+        let plan = tactics().plan(&field, 0, &wugongs, &[]);
+        assert_eq!(plan.act, Act::Attack((20, 10), 1));
+        assert_eq!(plan.to, (13, 10));
+        assert!(best_skill(&field, 0, plan.to, (20, 10), &wugongs).is_some());
+        assert!(best_skill(&field, 0, (10, 10), (20, 10), &wugongs).is_none());
+        // next to it the stronger one
+        let near = open_field(vec![
+            unit(Side::Enemy, (10, 10), 100, 45),
+            unit(Side::Ally, (12, 10), 100, 45),
+        ]);
+        let plan = tactics().plan(&near, 0, &wugongs, &[]);
+        assert_eq!(plan.act, Act::Attack((12, 10), 0));
+    }
+
+    #[test]
+    fn heals_and_backs_off() {
+        let herb = record::<Thing>(&[("add_life", 0, 20)]);
+        let pill = record::<Thing>(&[("add_life", 0, 50)]);
+        let antidote = record::<Thing>(&[("add_detox", 0, -30)]);
+        let things = [herb, pill, antidote];
+        let mut units = vec![
+            unit(Side::Enemy, (10, 10), 20, 45),
+            unit(Side::Ally, (11, 10), 100, 45),
+        ];
+        units[0].items = vec![(0, 2), (2, 1), (1, 1)];
+        let mut field = open_field(units);
+        let wugongs = [plain(100, 10)];
+
+        // low on life, the best medicine it has
+        let plan = tactics().plan(&field, 0, &wugongs, &things);
+        assert_eq!(
+            plan,
+            Plan {
+                to: (10, 10),
+                act: Act::Item(2)
+            }
+        );
+        // poisoned
+        field.units[0].stats.life = 100;
+        field.units[0].stats.tox = 50;
+        let plan = tactics().plan(&field, 0, &wugongs, &things);
+        assert_eq!(plan.act, Act::Item(1));
+        field.units[0].stats.tox = 0;
+        assert_eq!(
+            tactics().plan(&field, 0, &wugongs, &things).act,
+            Act::Attack((11, 10), 0)
+        );
+
+        // badly wounded it walks as far as it gets
+        field.units[0].stats.injure = 60;
+        let plan = tactics().plan(&field, 0, &wugongs, &things);
+        assert_eq!(plan.act, Act::Wait);
+        assert_eq!(field.units[0].steps(), 2);
+        assert_eq!(plan.to, (8, 10));
+        // the easy one does not care
+        assert_eq!(
+            Reckless.plan(&field, 0, &wugongs, &things).act,
+            Act::Attack((11, 10), 0)
+        );
+    }
+
+    #[test]
+    fn difficulties() {
+        assert_eq!("Hard".parse(), Ok(Difficulty::Hard));
+        assert!("brutal".parse::<Difficulty>().is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
+
+use bevy::prelude::*;
+
+use crate::game::sound::SoundEffect;
+use crate::game::structs::{
+    MoveDir, Palette, Person, TextureMap, TextureMeta, Wugong, ACTION_FRAME,
+};
+use crate::game::GrpAsset;
+
+use super::{depth, tile, Battle, BattleScreen, Phase, UnitSprite};
+
+// a point of 出招动画延迟
+const DELAY_SECS: f32 = 0.02;
+// the delay between two frames of an effect
+const EFFECT_SECS: f32 = 0.05;
+
+// CC.Effect of jyconst.lua, the frames of every effect of eft.grp in a row
+pub const EFFECT_FRAMES: [usize; 110] = [
+    9, 17, 17, 17, 18, 13, 20, 17, 18, 16, 16, 12, 14, 17, 14, 19, 13, 14, 23, 24, 16, 19, 13, 14,
+    17, 9, 15, 14, 16, 9, 15, 14, 16, 15, 19, 14, 13, 17, 13, 17, 16, 15, 15, 17, 16, 9, 13, 15,
+    10, 10, 21, 12, 20, 14, 17, 14, 11, 16, 17, 17, 18, 12, 10, 13, 10, 11, 11, 8, 11, 17, 11, 15,
+    11, 11, 13, 13, 15, 13, 16, 17, 9, 12, 11, 20, 12, 14, 12, 13, 16, 16, 11, 16, 10, 12, 9, 13,
+    9, 14, 10, 10, 13, 19, 12, 8, 12, 10, 11, 16, 18, 20,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PicFile {
+    // fight{avatar}.grp, the attacks of a person
+    Fight(usize),
+    // eft.grp, the wugong effects
+    Effect,
+}
+
+impl PicFile {
+    fn path(&self) -> String {
+        match self {
+            PicFile::Fight(avatar) => format!("org/data/fight{:03}.grp", avatar),
+            PicFile::Effect => "org/data/eft.grp".into(),
+        }
+    }
+}
+
+/// The fight pictures of a battle, loaded when it starts and decoded when shown.
+pub struct FightPics {
+    loading: Vec<(PicFile, Handle<GrpAsset>)>,
+    files: HashMap<PicFile, TextureMap>,
+    images: HashMap<(PicFile, usize), (Handle<Image>, TextureMeta)>,
+}
+
+impl FightPics {
+    pub fn load(asset_server: &AssetServer, files: impl Iterator<Item = PicFile>) -> Self {
+        FightPics {
+            loading: files
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .map(|v| (v, asset_server.load(v.path().as_str())))
+                .collect(),
+            files: HashMap::default(),
+            images: HashMap::default(),
+        }
+    }
+
+    // a picture decoded once, None while its file loads or when it is missing
+    fn image(
+        &mut self,
+        file: PicFile,
+        pic: usize,
+        palette: &Palette,
+        images: &mut Assets<Image>,
+    ) -> Option<(Handle<Image>, TextureMeta)> {
+        if let Some((handle, meta)) = self.images.get(&(file, pic)) {
+            return Some((handle.clone(), *meta));
+        }
+        match self.files.get(&file)?.get_image(pic, &palette.0) {
+            Ok(Some((image, meta))) => {
+                let handle = images.add(image);
+                self.images.insert((file, pic), (handle.clone(), meta));
+                Some((handle, meta))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!("no picture {} in {}: {}", pic, file.path(), e);
+                None
+            }
+        }
+    }
+}
+
+/// The frames of an attack in fight{avatar}.grp and the one its sound plays at.
+#[derive(Debug, PartialEq)]
+pub struct Swing {
+    pub pics: Range<usize>,
+    pub secs: f32,
+    pub sound_at: usize,
+}
+
+/// The attack of wugong type `typ` (1 拳 2 剑 3 刀 4 特殊) facing `facing`.
+///
+/// The kinds follow each other with their frames for the 4 facings, a kind the person
+/// has no frames for falls back to the first one it has.
+pub fn swing(person: &Person, typ: i16, facing: MoveDir) -> Option<Swing> {
+    let frames = |kind| person.action_frames(kind).max(0) as usize;
+    let kind = (typ - 1).clamp(0, ACTION_FRAME as i16 - 1) as usize;
+    let kind = if frames(kind) > 0 {
+        kind
+    } else {
+        (0..ACTION_FRAME).find(|v| frames(*v) > 0)?
+    };
+    let n = frames(kind);
+    let start = (0..kind).map(|v| frames(v) * 4).sum::<usize>() + facing.to_dir() as usize * n;
+    Some(Swing {
+        pics: start..start + n,
+        secs: person.action_delay(kind).max(1) as f32 * DELAY_SECS,
+        sound_at: (person.action_audio_delay(kind).max(1) as usize - 1).min(n - 1),
+    })
+}
+
+/// The pictures of effect `eft` in eft.grp, `frames` counts the frames of every effect.
+pub fn effect(frames: &[usize], eft: usize) -> Option<Range<usize>> {
+    let n = *frames.get(eft)?;
+    let start = frames[..eft].iter().sum::<usize>();
+    (n > 0).then(|| start..start + n)
+}
+
+/// Where a unit at `from` looks to face `to`.
+pub fn facing(from: (usize, usize), to: (usize, usize)) -> MoveDir {
+    let dx = to.0 as i32 - from.0 as i32;
+    let dy = to.1 as i32 - from.1 as i32;
+    match (dx.abs() >= dy.abs(), dx > 0, dy > 0) {
+        (true, true, _) => MoveDir::Right,
+        (true, false, _) => MoveDir::Left,
+        (false, _, true) => MoveDir::Down,
+        (false, _, false) => MoveDir::Up,
+    }
+}
+
+/// A picture shown over a tile, its sound starts with it.
+#[derive(Debug)]
+pub struct Frame {
+    file: PicFile,
+    pic: usize,
+    pos: (usize, usize),
+    secs: f32,
+    sound: Option<String>,
+}
+
+/// The swing of the attacker then the effect of the wugong over the target.
+pub fn fight_frames(
+    person: &Person,
+    wugong: &Wugong,
+    from: (usize, usize),
+    to: (usize, usize),
+    effect_frames: &[usize],
+) -> VecDeque<Frame> {
+    let mut frames = VecDeque::new();
+    if let Some(swing) = swing(person, wugong.typ(), facing(from, to)) {
+        let start = swing.pics.start;
+        frames.extend(swing.pics.map(|pic| Frame {
+            file: PicFile::Fight(person.avatar().max(0) as usize),
+            pic,
+            pos: from,
+            secs: swing.secs,
+            sound: (pic - start == swing.sound_at).then(|| format!("atk{:02}.wav", wugong.sound())),
+        }));
+    }
+    let eft = wugong.animation().max(0) as usize;
+    if let Some(pics) = effect(effect_frames, eft) {
+        let start = pics.start;
+        frames.extend(pics.map(|pic| Frame {
+            file: PicFile::Effect,
+            pic,
+            pos: to,
+            secs: EFFECT_SECS,
+            sound: (pic == start).then(|| format!("e{:02}.wav", eft)),
+        }));
+    }
+    frames
+}
+
+/// Moves the loaded fight files into `FightPics`.
+pub fn load_pics(
+    asset_server: Res<AssetServer>,
+    mut grp_assets: ResMut<Assets<GrpAsset>>,
+    mb_pics: Option<ResMut<FightPics>>,
+) {
+    let mut pics = match mb_pics {
+        Some(v) if !v.loading.is_empty() => v,
+        _ => return,
+    };
+    let pics = &mut *pics;
+    pics.loading.retain(|(file, handle)| {
+        match asset_server.get_load_state(handle) {
+            bevy::asset::LoadState::Loaded => {
+                if let Some(gs) = grp_assets.remove(handle) {
+                    pics.files.insert(*file, TextureMap::new(gs));
+                }
+            }
+            bevy::asset::LoadState::Failed => warn!("no fight pictures in {}", file.path()),
+            _ => return true,
+        }
+        false
+    });
+}
+
+/// Plays the frames of the fight one after the other, the hits land after the last one.
+pub fn play_fight(
+    mut commands: Commands,
+    time: Res<Time>,
+    mb_battle: Option<ResMut<Battle>>,
+    mut pics: Option<ResMut<FightPics>>,
+    palette: Option<Res<Palette>>,
+    mut images: ResMut<Assets<Image>>,
+    mut sounds: EventWriter<SoundEffect>,
+) {
+    let mut battle = match mb_battle {
+        Some(v) => v,
+        None => return,
+    };
+    let Battle {
+        phase, field, view, ..
+    } = &mut *battle;
+    let fight = match phase {
+        Phase::Fight(v) => v,
+        _ => return,
+    };
+    if !fight.timer.tick(time.delta()).finished() {
+        return;
+    }
+    if let Some((entity, _)) = fight.shown.take() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let frame = match fight.frames.pop_front() {
+        Some(v) => v,
+        None => {
+            field.attack_all(fight.unit, &fight.hits);
+            *phase = Phase::Next;
+            return;
+        }
+    };
+    if let Some(sound) = frame.sound {
+        sounds.send(SoundEffect(sound));
+    }
+    fight.timer = Timer::from_seconds(frame.secs, false);
+    let image = match (pics.as_mut(), palette.as_ref()) {
+        (Some(pics), Some(palette)) => pics.image(frame.file, frame.pic, palette, &mut images),
+        _ => None,
+    };
+    if let Some((texture, meta)) = image {
+        let mut translation = tile(frame.pos, 2. + depth(frame.pos));
+        translation.x -= meta.2 - meta.0 as f32 / 2.;
+        translation.y += meta.3 - meta.1 as f32 / 2.;
+        let entity = commands
+            .spawn_bundle(SpriteBundle {
+                texture,
+                transform: Transform::from_translation(translation),
+                ..Default::default()
+            })
+            .insert(BattleScreen)
+            .id();
+        commands.entity(*view).push_children(&[entity]);
+        fight.shown = Some((entity, frame.file));
+    }
+}
+
+/// The attacker's portrait makes way for its swing.
+pub fn is_swinging(battle: &Battle, sprite: &UnitSprite) -> bool {
+    match &battle.phase {
+        Phase::Fight(fight) => {
+            fight.unit == sprite.0 && matches!(fight.shown, Some((_, PicFile::Fight(_))))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::record;
+
+    #[test]
+    fn swing_frames() {
+        // 3 frames of 拳, none of 剑 and 5 of 刀
+        let person = record::<Person>(&[
+            ("action_frames", 0, 3),
+            ("action_frames", 2, 5),
+            ("action_delays", 2, 4),
+            ("action_audio_delays", 2, 2),
+        ]);
+        let swing = swing(&person, 3, MoveDir::Left).unwrap();
+        assert_eq!(swing.pics, 22..27);
+        assert_eq!(swing.sound_at, 1);
+        assert!((swing.secs - 4. * DELAY_SECS).abs() < f32::EPSILON);
+        // 剑 falls back to 拳
+        assert_eq!(super::swing(&person, 2, MoveDir::Up).unwrap().pics, 0..3);
+        assert_eq!(super::swing(&person, 1, MoveDir::Down).unwrap().pics, 9..12);
+        assert_eq!(super::swing(&record::<Person>(&[]), 1, MoveDir::Up), None);
+    }
+
+    #[test]
+    fn effects_and_facing() {
+        let frames = [9, 17, 0, 12];
+        assert_eq!(effect(&frames, 0), Some(0..9));
+        assert_eq!(effect(&frames, 3), Some(26..38));
+        assert_eq!(effect(&frames, 2), None);
+        assert_eq!(effect(&frames, 4), None);
+        assert_eq!(effect(&EFFECT_FRAMES, 1), Some(9..26));
+
+        assert_eq!(facing((5, 5), (6, 5)), MoveDir::Right);
+        assert_eq!(facing((5, 5), (5, 4)), MoveDir::Up);
+        assert_eq!(facing((5, 5), (3, 4)), MoveDir::Left);
+        assert_eq!(facing((5, 5), (5, 6)), MoveDir::Down);
+    }
+}
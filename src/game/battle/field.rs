@@ -0,0 +1,529 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::game::combat::{level_in_use, Hit, Stats, INJURE_MAX, TOX_MAX};
+use crate::game::structs::{Person, Thing, Wugong, WAR_HEIGHT, WAR_WIDTH};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Side {
+    Ally,
+    Enemy,
+}
+
+/// A person on the battle map, the stats are copied from its record when the battle starts.
+#[derive(Debug, Clone)]
+pub struct Unit {
+    // the index of the person record
+    pub person: usize,
+    pub side: Side,
+    pub pos: (usize, usize),
+    pub stats: Stats,
+    // the learned wugongs as (wugong id, table index of the level)
+    pub skills: Vec<(usize, usize)>,
+    // the carried things as (thing id, count)
+    pub items: Vec<(usize, i16)>,
+}
+
+impl Unit {
+    pub fn new(
+        person: usize,
+        p: &Person,
+        things: &[Thing],
+        side: Side,
+        pos: (usize, usize),
+    ) -> Self {
+        Unit {
+            person,
+            side,
+            pos,
+            stats: Stats::of(p, things),
+            skills: p
+                .skills()
+                .map(|(id, level)| (id, Wugong::level_of(level)))
+                .collect(),
+            items: p.items().collect(),
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.stats.life > 0
+    }
+
+    /// Takes a medicine, its 生命, 内力 and 中毒程度 change.
+    pub fn take(&mut self, thing: &Thing) {
+        let me = &mut self.stats;
+        me.life = (me.life + thing.add_life()).clamp(0, me.life_max.max(0));
+        me.neili = (me.neili + thing.add_mag()).clamp(0, me.neili_max.max(0));
+        me.tox = (me.tox + thing.add_detox()).clamp(0, TOX_MAX);
+    }
+
+    /// The tiles it walks in a turn, 轻功/15 less 受伤程度/40 as in the original.
+    pub fn steps(&self) -> usize {
+        (self.stats.agile / 15 - self.stats.injure / 40).max(0) as usize
+    }
+}
+
+const DIRS: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+fn on_map(x: i32, y: i32) -> Option<(usize, usize)> {
+    if (0..WAR_WIDTH as i32).contains(&x) && (0..WAR_HEIGHT as i32).contains(&y) {
+        Some((x as usize, y as usize))
+    } else {
+        None
+    }
+}
+
+fn neighbours((x, y): (usize, usize)) -> impl Iterator<Item = (usize, usize)> {
+    DIRS.into_iter()
+        .filter_map(move |(dx, dy)| on_map(x as i32 + dx, y as i32 + dy))
+}
+
+// the tiles 1 to `len` away from `from` toward `dir`
+fn line(
+    from: (usize, usize),
+    (dx, dy): (i32, i32),
+    len: usize,
+) -> impl Iterator<Item = (usize, usize)> {
+    (1..=len as i32).filter_map(move |n| on_map(from.0 as i32 + dx * n, from.1 as i32 + dy * n))
+}
+
+// the tiles around `center` with |dx| and |dy| up to `r`, the ones `keep` accepts
+fn square(center: (usize, usize), r: i32, keep: impl Fn(i32, i32) -> bool) -> Vec<(usize, usize)> {
+    (-r..=r)
+        .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+        .filter(|(dx, dy)| keep(*dx, *dy))
+        .filter_map(|(dx, dy)| on_map(center.0 as i32 + dx, center.1 as i32 + dy))
+        .collect()
+}
+
+pub fn distance(a: (usize, usize), b: (usize, usize)) -> usize {
+    a.0.abs_diff(b.0) + a.1.abs_diff(b.1)
+}
+
+/// The tiles a wugong at table level `level` is aimed at from `from`, its 移动范围 away.
+///
+/// 0 点 and 3 面 are aimed at any tile that far, 1 线 and 2 十字 along the four lines.
+pub fn aims(wugong: &Wugong, level: usize, from: (usize, usize)) -> Vec<(usize, usize)> {
+    let reach = wugong.move_range(level).max(1) as i32;
+    match wugong.range_type() {
+        1 | 2 => DIRS
+            .into_iter()
+            .flat_map(|dir| line(from, dir, reach as usize))
+            .collect(),
+        _ => square(from, reach, |dx, dy| {
+            (dx, dy) != (0, 0) && dx.abs() + dy.abs() <= reach
+        }),
+    }
+}
+
+/// The tiles a wugong aimed at `aim` hits: 点 the tile, 面 the square of its 杀伤范围
+/// around it, 线 the line toward it and 十字 the four lines.
+pub fn area(
+    wugong: &Wugong,
+    level: usize,
+    from: (usize, usize),
+    aim: (usize, usize),
+) -> Vec<(usize, usize)> {
+    let reach = wugong.move_range(level).max(1) as usize;
+    match wugong.range_type() {
+        1 => {
+            let dx = (aim.0 as i32 - from.0 as i32).signum();
+            let dy = (aim.1 as i32 - from.1 as i32).signum();
+            line(from, (dx, dy), reach).collect()
+        }
+        2 => DIRS
+            .into_iter()
+            .flat_map(|dir| line(from, dir, reach))
+            .collect(),
+        3 => square(aim, wugong.kill_range(level).max(0) as i32, |_, _| true),
+        _ => vec![aim],
+    }
+}
+
+/// The tiles of a battle map and the units on them.
+pub struct Field {
+    // a building blocks its tile, WAR_WIDTH * WAR_HEIGHT by rows
+    blocked: Vec<bool>,
+    pub units: Vec<Unit>,
+}
+
+impl Field {
+    pub fn new(blocked: Vec<bool>, units: Vec<Unit>) -> Self {
+        Field { blocked, units }
+    }
+
+    /// The living unit on a tile.
+    pub fn unit_at(&self, pos: (usize, usize)) -> Option<usize> {
+        self.units.iter().position(|v| v.is_alive() && v.pos == pos)
+    }
+
+    fn is_free(&self, (x, y): (usize, usize)) -> bool {
+        !self.blocked[y * WAR_WIDTH + x] && self.unit_at((x, y)).is_none()
+    }
+
+    /// Where the unit can walk this turn and the steps it takes, its own tile included.
+    pub fn reachable(&self, unit: usize) -> HashMap<(usize, usize), usize> {
+        let start = self.units[unit].pos;
+        let steps = self.units[unit].steps();
+        let mut found = HashMap::new();
+        found.insert(start, 0);
+        let mut queue = VecDeque::from([start]);
+        while let Some(pos) = queue.pop_front() {
+            let n = found[&pos];
+            if n == steps {
+                continue;
+            }
+            for next in neighbours(pos) {
+                if !found.contains_key(&next) && self.is_free(next) {
+                    found.insert(next, n + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        found
+    }
+
+    /// The living units of a round, the more agile act first.
+    pub fn turn_order(&self) -> Vec<usize> {
+        let mut order = (0..self.units.len())
+            .filter(|v| self.units[*v].is_alive())
+            .collect::<Vec<_>>();
+        order.sort_by_key(|v| -self.units[*v].stats.agile);
+        order
+    }
+
+    /// The wugong of a skill of the unit and the level its 内力 is enough for.
+    pub fn skill<'a>(
+        &self,
+        unit: usize,
+        skill: usize,
+        wugongs: &'a [Wugong],
+    ) -> Option<(&'a Wugong, usize)> {
+        let me = &self.units[unit];
+        let (id, level) = *me.skills.get(skill)?;
+        let wugong = wugongs.get(id)?;
+        Some((wugong, level_in_use(wugong, level, me.stats.neili)))
+    }
+
+    /// The living opponents of the unit on the tiles.
+    pub fn targets_on(&self, unit: usize, tiles: &[(usize, usize)]) -> Vec<usize> {
+        let side = self.units[unit].side;
+        tiles
+            .iter()
+            .filter_map(|pos| self.unit_at(*pos))
+            .filter(|v| self.units[*v].side != side)
+            .collect()
+    }
+
+    /// How many steps the opponents of the unit walk to each tile, the unit out of the way.
+    /// The tiles they can not get to are left out.
+    pub fn distances(&self, unit: usize) -> HashMap<(usize, usize), usize> {
+        let me = &self.units[unit];
+        let mut found = HashMap::new();
+        let mut queue = VecDeque::new();
+        for v in self
+            .units
+            .iter()
+            .filter(|v| v.side != me.side && v.is_alive())
+        {
+            found.insert(v.pos, 0);
+            queue.push_back(v.pos);
+        }
+        while let Some(pos) = queue.pop_front() {
+            let n = found[&pos];
+            for next in neighbours(pos) {
+                let open = self.is_free(next) || next == me.pos;
+                if open && !found.contains_key(&next) {
+                    found.insert(next, n + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        found
+    }
+
+    /// Applies a hit of `unit` on `target`, returns the life it loses.
+    pub fn attack(&mut self, unit: usize, target: usize, hit: &Hit) -> i16 {
+        let t = &mut self.units[target].stats;
+        let hurt = hit.hurt.min(t.life);
+        t.life -= hurt;
+        t.injure = (t.injure + hit.injure).min(INJURE_MAX);
+        t.tox = (t.tox + hit.poison).min(TOX_MAX);
+        t.neili -= hit.neili_loss.min(t.neili);
+        let me = &mut self.units[unit].stats;
+        me.neili = (me.neili - hit.neili_cost + hit.neili_gain).clamp(0, me.neili_max.max(0));
+        hurt
+    }
+
+    /// Applies the hits of one attack on everyone in its area, the 内力 is spent once.
+    pub fn attack_all(&mut self, unit: usize, hits: &[(usize, Hit)]) {
+        for (i, (target, hit)) in hits.iter().enumerate() {
+            let cost = if i == 0 { hit.neili_cost } else { 0 };
+            self.attack(
+                unit,
+                *target,
+                &Hit {
+                    neili_cost: cost,
+                    ..*hit
+                },
+            );
+        }
+    }
+
+    /// Some(true) once every enemy is down, Some(false) once every ally is.
+    pub fn outcome(&self) -> Option<bool> {
+        let alive = |side| self.units.iter().any(|v| v.side == side && v.is_alive());
+        if !alive(Side::Enemy) {
+            Some(true)
+        } else if !alive(Side::Ally) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// The reachable tile the nearest opponent walks to in the fewest steps, around the
+    /// buildings, the shorter walk on a tie. A walled off unit goes by the straight distance.
+    pub fn approach(&self, unit: usize) -> (usize, usize) {
+        let me = &self.units[unit];
+        let opponents = self
+            .units
+            .iter()
+            .filter(|v| v.side != me.side && v.is_alive())
+            .map(|v| v.pos)
+            .collect::<Vec<_>>();
+        let walks = self.distances(unit);
+        self.reachable(unit)
+            .into_iter()
+            .min_by_key(|(pos, steps)| {
+                let nearest = opponents.iter().map(|v| distance(*pos, *v)).min();
+                let walk = walks.get(pos).copied().unwrap_or(usize::MAX);
+                (walk, nearest.unwrap_or(0), *steps, *pos)
+            })
+            .map_or(me.pos, |v| v.0)
+    }
+}
+
+// the battle fixtures of the tests here, in `ai` and in `combat`
+#[cfg(test)]
+pub(crate) mod fixture {
+    use super::*;
+    use crate::game::record;
+
+    // a fighter with the first two wugongs of the list, `agile` tells its steps
+    pub fn unit(side: Side, pos: (usize, usize), life: i16, agile: i16) -> Unit {
+        Unit {
+            person: 0,
+            side,
+            pos,
+            stats: Stats {
+                life,
+                life_max: 100,
+                neili: 100,
+                neili_max: 100,
+                attack: 40,
+                defence: 20,
+                agile,
+                ..Default::default()
+            },
+            skills: vec![(0, 0), (1, 0)],
+            items: vec![],
+        }
+    }
+
+    pub fn open_field(units: Vec<Unit>) -> Field {
+        Field::new(vec![false; WAR_WIDTH * WAR_HEIGHT], units)
+    }
+
+    // a wugong with the i16 fields set, (field, level, value)
+    pub fn wugong(fields: &[(&str, usize, i16)]) -> Wugong {
+        record(fields)
+    }
+
+    // a 点 wugong of the first level
+    pub fn plain(attack: i16, mp_cost: i16) -> Wugong {
+        wugong(&[("attacks", 0, attack), ("mp_cost", 0, mp_cost)])
+    }
+
+    // a 点 wugong aimed `moves` tiles away
+    pub fn ranged(attack: i16, moves: i16) -> Wugong {
+        wugong(&[("attacks", 0, attack), ("moves", 0, moves)])
+    }
+
+    // the shape of a wugong, 点 线 十字 面 by `range_type`
+    pub fn shaped(range_type: i16, moves: i16, ranges: i16) -> Wugong {
+        wugong(&[
+            ("range_type", 0, range_type),
+            ("moves", 0, moves),
+            ("ranges", 0, ranges),
+        ])
+    }
+
+    // a wugong stronger every level, `hurt_type` 1 drains neili
+    pub fn leveled(hurt_type: i16) -> Wugong {
+        let mut fields = vec![
+            ("hurt_type", 0, hurt_type),
+            ("mp_cost", 0, 30),
+            ("poison", 0, 2),
+        ];
+        for i in 0..10 {
+            fields.push(("attacks", i, 100 + 60 * i as i16));
+            fields.push(("add_mp", i, 10));
+            fields.push(("kill_mp", i, 20));
+        }
+        wugong(&fields)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::fixture::{open_field, shaped, unit};
+    use super::*;
+
+    #[test]
+    fn turn_order() {
+        let mut field = open_field(vec![
+            unit(Side::Ally, (0, 0), 50, 30),
+            unit(Side::Enemy, (5, 5), 50, 60),
+            unit(Side::Ally, (1, 0), 50, 30),
+            unit(Side::Enemy, (6, 5), 50, 90),
+        ]);
+        assert_eq!(field.turn_order(), [3, 1, 0, 2]);
+        field.units[1].stats.life = 0;
+        assert_eq!(field.turn_order(), [3, 0, 2]);
+    }
+
+    #[test]
+    fn reachable_tiles() {
+        let mut blocked = vec![false; WAR_WIDTH * WAR_HEIGHT];
+        // a wall east of (10, 10) and an enemy south of it
+        for y in 8..=12 {
+            blocked[y * WAR_WIDTH + 11] = true;
+        }
+        let field = Field::new(
+            blocked,
+            vec![
+                unit(Side::Ally, (10, 10), 50, 30),
+                unit(Side::Enemy, (10, 11), 50, 30),
+            ],
+        );
+        assert_eq!(field.units[0].steps(), 2);
+        let tiles = field.reachable(0);
+        assert_eq!(tiles[&(10, 10)], 0);
+        assert_eq!(tiles[&(9, 11)], 2);
+        assert_eq!(tiles[&(10, 8)], 2);
+        assert!(!tiles.contains_key(&(11, 10)));
+        assert!(!tiles.contains_key(&(10, 11)));
+        assert!(!tiles.contains_key(&(10, 12)));
+        assert_eq!(tiles.len(), 7);
+
+        // at the corner of the map
+        let field = open_field(vec![unit(Side::Ally, (0, 0), 50, 15)]);
+        assert_eq!(field.reachable(0).len(), 3);
+    }
+
+    #[test]
+    fn walk_around_a_wall() {
+        let mut blocked = vec![false; WAR_WIDTH * WAR_HEIGHT];
+        for y in 5..=15 {
+            blocked[y * WAR_WIDTH + 11] = true;
+        }
+        let field = Field::new(
+            blocked,
+            vec![
+                unit(Side::Ally, (10, 10), 50, 30),
+                unit(Side::Enemy, (13, 10), 50, 45),
+            ],
+        );
+        let walks = field.distances(1);
+        assert_eq!(walks[&(10, 10)], 0);
+        assert_eq!(walks[&(12, 10)], 14);
+        assert_eq!(walks[&(13, 10)], 15);
+        // the straight way ends at the wall
+        assert_eq!(field.approach(1), (12, 8));
+    }
+
+    #[test]
+    fn wugong_ranges() {
+        let from = (10, 10);
+        // 点, a 移动范围 of 0 still reaches the next tile
+        let point = shaped(0, 0, 0);
+        assert_eq!(aims(&point, 0, from).len(), 4);
+        let point = shaped(0, 2, 0);
+        assert_eq!(aims(&point, 0, from).len(), 12);
+        assert!(aims(&point, 0, from).contains(&(11, 11)));
+        assert_eq!(area(&point, 0, from, (12, 10)), [(12, 10)]);
+        // 线 toward the aim
+        let beam = shaped(1, 3, 0);
+        assert_eq!(aims(&beam, 0, from).len(), 12);
+        assert_eq!(area(&beam, 0, from, (10, 9)), [(10, 9), (10, 8), (10, 7)]);
+        // 十字 all around
+        let cross = shaped(2, 2, 0);
+        assert_eq!(area(&cross, 0, from, (12, 10)).len(), 8);
+        // 面 around the aim, cut by the edge of the map
+        let blast = shaped(3, 3, 1);
+        assert_eq!(area(&blast, 0, from, (13, 10)).len(), 9);
+        assert_eq!(area(&blast, 0, (1, 0), (0, 0)).len(), 4);
+
+        // the hits reach the opponents in the area only
+        let mut field = open_field(vec![
+            unit(Side::Ally, from, 50, 30),
+            unit(Side::Enemy, (13, 10), 50, 30),
+            unit(Side::Enemy, (14, 11), 50, 30),
+            unit(Side::Ally, (12, 10), 50, 30),
+        ]);
+        assert_eq!(
+            field.targets_on(0, &area(&blast, 0, from, (13, 10))),
+            [1, 2]
+        );
+        let hit = Hit {
+            hurt: 10,
+            neili_cost: 20,
+            ..Default::default()
+        };
+        field.attack_all(0, &[(1, hit), (2, hit)]);
+        assert_eq!(field.units[1].stats.life, 40);
+        assert_eq!(field.units[2].stats.life, 40);
+        // spent once
+        assert_eq!(field.units[0].stats.neili, 80);
+    }
+
+    #[test]
+    fn fight_to_the_end() {
+        let mut field = open_field(vec![
+            unit(Side::Ally, (0, 0), 50, 30),
+            unit(Side::Enemy, (4, 0), 50, 30),
+        ]);
+        assert_eq!(field.outcome(), None);
+        let next_to = |field: &Field| {
+            let tiles = neighbours(field.units[1].pos).collect::<Vec<_>>();
+            field.targets_on(1, &tiles)
+        };
+        assert!(next_to(&field).is_empty());
+
+        field.units[1].pos = field.approach(1);
+        assert_eq!(field.units[1].pos, (2, 0));
+        field.units[1].pos = field.approach(1);
+        assert_eq!(field.units[1].pos, (1, 0));
+        assert_eq!(next_to(&field), [0]);
+        // next to it already
+        assert_eq!(field.approach(1), (1, 0));
+
+        let hit = Hit {
+            hurt: 11,
+            injure: 1,
+            poison: 2,
+            neili_cost: 30,
+            ..Default::default()
+        };
+        assert_eq!(field.attack(1, 0, &hit), 11);
+        let (me, target) = (&field.units[1].stats, &field.units[0].stats);
+        assert_eq!((target.life, target.injure, target.tox), (39, 1, 2));
+        assert_eq!(me.neili, 70);
+        field.units[0].stats.life = 5;
+        field.units[1].stats.neili = 10;
+        assert_eq!(field.attack(1, 0, &hit), 5);
+        assert_eq!(field.units[1].stats.neili, 0);
+        assert_eq!(field.outcome(), Some(false));
+        assert_eq!(field.unit_at((0, 0)), None);
+    }
+}
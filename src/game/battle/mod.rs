@@ -0,0 +1,799 @@
+use std::collections::{HashSet, VecDeque};
+
+use bevy::prelude::*;
+
+use ai::{Act, Brain, Difficulty};
+use anim::{FightPics, PicFile};
+pub use field::{Field, Side, Unit};
+
+use crate::game::combat::{self, Hit};
+use crate::game::encoding::TextEncoding;
+use crate::game::script::{spawn_choice, Answer, Choice, ChoiceRow, DialogBox, EventScript};
+use crate::game::smap::{Me, NetCell};
+use crate::game::structs::*;
+use crate::game::util::{despawn_screen, ImageCache, PosXY, RenderHelper};
+use crate::game::GameState;
+use crate::settings::Settings;
+
+pub mod ai;
+mod anim;
+mod field;
+
+#[cfg(test)]
+pub(crate) use field::fixture;
+
+// the pause before an enemy acts, to see what it does
+const ENEMY_SECS: f32 = 0.3;
+// Thing 类型 3 药品
+const MEDICINE: i16 = 3;
+
+pub struct Plugin;
+
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::Battle).with_system(setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::Battle)
+                    .with_system(next_turn.label("turn"))
+                    .with_system(unit_menu.after("turn"))
+                    .with_system(pick_tile.after("turn"))
+                    .with_system(enemy_turn.after("turn"))
+                    .with_system(anim::load_pics)
+                    .with_system(anim::play_fight.after("turn"))
+                    .with_system(finish.after("turn"))
+                    .with_system(update_view.after("turn")),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Battle)
+                    .with_system(despawn_screen::<BattleScreen>)
+                    .with_system(show_map),
+            );
+    }
+}
+
+/// The battle `instruct_6` asks for, taken by `setup`.
+pub struct BattleRequest {
+    // the index in war.sta
+    pub war: usize,
+    // a lost battle still gives its exp
+    pub exp_on_defeat: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Action {
+    Move,
+    Attack,
+    Item,
+    Wait,
+}
+
+impl Action {
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Move => "移动",
+            Action::Attack => "攻击",
+            Action::Item => "物品",
+            Action::Wait => "等待",
+        }
+    }
+}
+
+// what the battle waits for
+enum Phase {
+    // the next unit of the round acts
+    Next,
+    // the action menu of an ally
+    Menu(usize, Vec<Action>),
+    // the cursor of an ally picks where to move or whom to hit
+    Target(usize, Action),
+    // the medicine of the backpack an ally may take
+    Item(usize, Vec<i16>),
+    // an enemy acts once the timer is done
+    Enemy(usize, Timer),
+    // an attack is played
+    Fight(Fight),
+    // the victory or the defeat is shown until a key is pressed
+    Over(bool),
+}
+
+// an attack being played, the hits land after its last frame
+struct Fight {
+    unit: usize,
+    // the opponents in its area and what it does to each
+    hits: Vec<(usize, Hit)>,
+    frames: VecDeque<anim::Frame>,
+    timer: Timer,
+    // the frame on screen and its file
+    shown: Option<(Entity, PicFile)>,
+}
+
+/// The running battle.
+pub struct Battle {
+    war: usize,
+    exp_on_defeat: bool,
+    field: Field,
+    // the units yet to act this round, the next one last
+    order: Vec<usize>,
+    // the acting unit moved already
+    moved: bool,
+    cursor: (usize, usize),
+    phase: Phase,
+    view: Entity,
+    // plays the enemies
+    brain: Box<dyn Brain>,
+}
+
+#[derive(Component)]
+pub struct BattleScreen;
+
+// the parent of the tiles and the units, moved to keep the acting unit in the middle
+#[derive(Component)]
+struct BattleView;
+
+// the unit and how far its picture is lifted from the tile
+#[derive(Component)]
+struct UnitSprite(usize, f32);
+
+#[derive(Component)]
+struct Cursor;
+
+// a tile the acting unit may pick
+#[derive(Component)]
+struct Reach;
+
+#[derive(Component)]
+struct BattleHud;
+
+// what the scene below the battle shows
+type SceneTiles = (Or<(With<NetCell>, With<Me>)>, Without<BattleScreen>);
+type Marks = Or<(With<Cursor>, With<Reach>)>;
+type CursorOnly = (With<Cursor>, Without<BattleView>, Without<UnitSprite>);
+
+// the map is drawn around the first tile, `BattleView` moves it
+fn tile((x, y): (usize, usize), z: f32) -> Vec3 {
+    PosXY::new(0, 0).to_real(x as f32, y as f32, z)
+}
+
+// the tiles in front cover the ones behind
+fn depth((x, y): (usize, usize)) -> f32 {
+    (x + y) as f32 / (WAR_WIDTH + WAR_HEIGHT) as f32
+}
+
+fn setup(
+    mut commands: Commands,
+    request: Res<BattleRequest>,
+    wars: Res<Vec<WarSta>>,
+    war_fld: Res<WarFld>,
+    people: Res<Vec<Person>>,
+    things: Res<Vec<Thing>>,
+    base: Res<Base>,
+    settings: Option<Res<Settings>>,
+    encoding: Res<TextEncoding>,
+    asset_server: Res<AssetServer>,
+    mut render_helper: ResMut<RenderHelper>,
+    mut image_cache: ResMut<ImageCache>,
+    mut map: Query<&mut Visibility, SceneTiles>,
+) {
+    commands.remove_resource::<BattleRequest>();
+    let war = &wars[request.war];
+    let map_id = war.map.max(0) as usize;
+    debug!(
+        "battle {} {} on map {}",
+        request.war,
+        war.name(*encoding),
+        map_id
+    );
+    // the scene waits below
+    for mut v in map.iter_mut() {
+        v.is_visible = false;
+    }
+
+    let blocked = (0..WAR_HEIGHT)
+        .flat_map(|y| (0..WAR_WIDTH).map(move |x| (x, y)))
+        .map(|(x, y)| war_fld.get_texture(map_id, x, y, 1).map_or(true, |v| v > 0))
+        .collect();
+    // the allies the battle names, the team when it names none
+    let allies = if war.auto_allies.iter().any(|v| *v >= 0) {
+        war.auto_allies
+    } else {
+        base.teams
+    };
+    let at = |x: i16, y: i16| {
+        (
+            (x.max(0) as usize).min(WAR_WIDTH - 1),
+            (y.max(0) as usize).min(WAR_HEIGHT - 1),
+        )
+    };
+    let units = allies
+        .iter()
+        .zip(war.ally_x.iter().zip(war.ally_y.iter()))
+        .map(|(p, (x, y))| (*p, Side::Ally, at(*x, *y)))
+        .chain(
+            war.enemies
+                .iter()
+                .zip(war.enemy_x.iter().zip(war.enemy_y.iter()))
+                .map(|(p, (x, y))| (*p, Side::Enemy, at(*x, *y))),
+        )
+        .filter(|(p, ..)| *p >= 0)
+        .filter_map(|(p, side, pos)| {
+            people
+                .get(p as usize)
+                .map(|person| Unit::new(p as usize, person, &things, side, pos))
+        })
+        .collect::<Vec<_>>();
+
+    let view = commands
+        .spawn_bundle((
+            Transform::default(),
+            GlobalTransform::default(),
+            BattleView,
+            BattleScreen,
+        ))
+        .id();
+    let mut children = vec![];
+    for y in 0..WAR_HEIGHT {
+        for x in 0..WAR_WIDTH {
+            for layer in 0..WAR_LAYER_NUM {
+                let id = war_fld.get_texture(map_id, x, y, layer).unwrap_or(0);
+                if id <= 0 {
+                    continue;
+                }
+                let z = if layer == 0 { 0. } else { 1. + depth((x, y)) };
+                let transform = Transform::from_translation(tile((x, y), z));
+                if let Some(v) =
+                    render_helper.render(&mut commands, MapType::Wmap, id as usize / 2, transform)
+                {
+                    commands.entity(v).insert(BattleScreen);
+                    children.push(v);
+                }
+            }
+        }
+    }
+    // the head portraits stand for the people
+    for (i, unit) in units.iter().enumerate() {
+        let avatar = people[unit.person].avatar().max(0) as usize;
+        if let Some((image_h, meta, _)) = image_cache.get_image(MapType::Head, avatar) {
+            let lift = meta.1 as f32 / 4.;
+            let color = match unit.side {
+                Side::Ally => Color::WHITE,
+                Side::Enemy => Color::rgb(1., 0.7, 0.7),
+            };
+            let entity = commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        ..Default::default()
+                    },
+                    texture: image_h,
+                    transform: Transform::from_scale(Vec3::new(0.5, 0.5, 1.)),
+                    ..Default::default()
+                })
+                .insert(UnitSprite(i, lift))
+                .insert(BattleScreen)
+                .id();
+            children.push(entity);
+        }
+    }
+    commands.entity(view).push_children(&children);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(10.)),
+                ..Default::default()
+            },
+            color: Color::rgba(0., 0., 0., 0.85).into(),
+            ..Default::default()
+        })
+        .insert(BattleScreen)
+        .with_children(|p| {
+            p.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    war.name(*encoding),
+                    TextStyle {
+                        font: asset_server.load("fonts/simsun.ttf"),
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .insert(BattleHud);
+        });
+
+    let files = units
+        .iter()
+        .map(|v| PicFile::Fight(people[v.person].avatar().max(0) as usize))
+        .chain([PicFile::Effect]);
+    commands.insert_resource(FightPics::load(&asset_server, files));
+    commands.insert_resource(Battle {
+        war: request.war,
+        exp_on_defeat: request.exp_on_defeat,
+        field: Field::new(blocked, units),
+        order: vec![],
+        moved: false,
+        cursor: (0, 0),
+        phase: Phase::Next,
+        view,
+        brain: settings
+            .map_or(Difficulty::Normal, |v| v.difficulty())
+            .brain(),
+    });
+}
+
+fn show_map(mut map: Query<&mut Visibility, SceneTiles>) {
+    for mut v in map.iter_mut() {
+        v.is_visible = true;
+    }
+}
+
+fn open_menu(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    battle: &mut Battle,
+    unit: usize,
+) {
+    let actions = [Action::Move, Action::Attack, Action::Item, Action::Wait]
+        .into_iter()
+        .filter(|v| *v != Action::Move || !battle.moved)
+        .filter(|v| *v != Action::Attack || !battle.field.units[unit].skills.is_empty())
+        .collect::<Vec<_>>();
+    let names = actions.iter().map(|v| v.name().to_string()).collect();
+    spawn_choice(commands, asset_server, "行动".into(), Choice::new(names));
+    battle.cursor = battle.field.units[unit].pos;
+    battle.phase = Phase::Menu(unit, actions);
+}
+
+// hits the area of a skill of the unit aimed at `aim`, the hits land once the attack is played
+fn strike(
+    field: &Field,
+    person: &Person,
+    wugongs: &[Wugong],
+    effect_frames: &[usize],
+    unit: usize,
+    aim: (usize, usize),
+    skill: usize,
+) -> Phase {
+    let (wugong, level) = match field.skill(unit, skill, wugongs) {
+        Some(v) => v,
+        None => return Phase::Next,
+    };
+    let me = &field.units[unit];
+    let mut rng = rand::thread_rng();
+    let hits = field
+        .targets_on(unit, &field::area(wugong, level, me.pos, aim))
+        .into_iter()
+        .map(|t| {
+            let them = &field.units[t];
+            let d = field::distance(me.pos, them.pos);
+            let hit = combat::hit(&mut rng, &me.stats, &them.stats, wugong, level, d);
+            (t, hit)
+        })
+        .collect();
+    Phase::Fight(Fight {
+        unit,
+        hits,
+        frames: anim::fight_frames(person, wugong, me.pos, aim, effect_frames),
+        timer: Timer::from_seconds(0., false),
+        shown: None,
+    })
+}
+
+// marks the tiles to pick from and puts the cursor on the acting unit
+fn show_tiles(commands: &mut Commands, battle: &Battle, tiles: Vec<(usize, usize)>) {
+    let mark = |color, size| SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(size),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut marks = tiles
+        .into_iter()
+        .map(|pos| {
+            let mut bundle = mark(Color::rgba(0.3, 0.6, 1., 0.4), Vec2::new(XSCALE, YSCALE));
+            bundle.transform = Transform::from_translation(tile(pos, 0.5));
+            commands
+                .spawn_bundle(bundle)
+                .insert(Reach)
+                .insert(BattleScreen)
+                .id()
+        })
+        .collect::<Vec<_>>();
+    let cursor = mark(Color::rgba(1., 1., 1., 0.6), Vec2::new(XSCALE, YSCALE));
+    marks.push(
+        commands
+            .spawn_bundle(cursor)
+            .insert(Cursor)
+            .insert(BattleScreen)
+            .id(),
+    );
+    commands.entity(battle.view).push_children(&marks);
+}
+
+fn spawn_over(commands: &mut Commands, asset_server: &AssetServer, win: bool) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(BattleScreen)
+        .with_children(|p| {
+            p.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    if win { "战斗胜利" } else { "战斗失败" },
+                    TextStyle {
+                        font: asset_server.load("fonts/simsun.ttf"),
+                        font_size: 48.0,
+                        color: Color::ORANGE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+// starts the turn of the next unit, a new round ordered by 轻功 when every unit acted
+fn next_turn(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mb_battle: Option<ResMut<Battle>>,
+) {
+    let mut battle = match mb_battle {
+        Some(v) if matches!(v.phase, Phase::Next) => v,
+        _ => return,
+    };
+    if let Some(win) = battle.field.outcome() {
+        spawn_over(&mut commands, &asset_server, win);
+        battle.phase = Phase::Over(win);
+        return;
+    }
+    let unit = loop {
+        if battle.order.is_empty() {
+            let mut order = battle.field.turn_order();
+            order.reverse();
+            battle.order = order;
+        }
+        let v = battle.order.pop().unwrap();
+        // the fallen lose their turn
+        if battle.field.units[v].is_alive() {
+            break v;
+        }
+    };
+    battle.moved = false;
+    match battle.field.units[unit].side {
+        Side::Ally => open_menu(&mut commands, &asset_server, &mut battle, unit),
+        Side::Enemy => {
+            battle.cursor = battle.field.units[unit].pos;
+            battle.phase = Phase::Enemy(unit, Timer::from_seconds(ENEMY_SECS, false));
+        }
+    }
+}
+
+// the action menu and the medicine list of an ally
+fn unit_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mb_battle: Option<ResMut<Battle>>,
+    mb_choice: Option<Res<Choice>>,
+    mut rows: Query<(&ChoiceRow, &mut Text)>,
+    boxes: Query<Entity, With<DialogBox>>,
+    mut backpack: ResMut<Backpack>,
+    things: Res<Vec<Thing>>,
+    encoding: Res<TextEncoding>,
+    wugongs: Res<Vec<Wugong>>,
+) {
+    let (mut battle, choice) = match (mb_battle, mb_choice) {
+        (Some(battle), Some(choice))
+            if matches!(battle.phase, Phase::Menu(..) | Phase::Item(..)) =>
+        {
+            (battle, choice)
+        }
+        _ => return,
+    };
+    let picked = match choice.update(&mut commands, &mut keyboard_input, &mut rows) {
+        Some(v) => v,
+        None => return,
+    };
+    for entity in boxes.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let battle = &mut *battle;
+    match std::mem::replace(&mut battle.phase, Phase::Next) {
+        Phase::Menu(unit, actions) => match picked.and_then(|v| actions.get(v)) {
+            Some(Action::Move) => {
+                let tiles = battle.field.reachable(unit).into_keys().collect();
+                show_tiles(&mut commands, battle, tiles);
+                battle.phase = Phase::Target(unit, Action::Move);
+            }
+            Some(Action::Attack) => {
+                // where any of its wugongs is aimed at
+                let pos = battle.field.units[unit].pos;
+                let tiles = (0..battle.field.units[unit].skills.len())
+                    .filter_map(|i| battle.field.skill(unit, i, &wugongs))
+                    .flat_map(|(wugong, level)| field::aims(wugong, level, pos))
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .collect();
+                show_tiles(&mut commands, battle, tiles);
+                battle.phase = Phase::Target(unit, Action::Attack);
+            }
+            Some(Action::Item) => {
+                let (ids, names): (Vec<_>, Vec<_>) = backpack
+                    .items
+                    .iter()
+                    .filter_map(|(id, num)| {
+                        let thing = things.get(*id as usize)?;
+                        (thing.typ() == MEDICINE)
+                            .then(|| (*id, format!("{} {}", thing.name(*encoding), num)))
+                    })
+                    .unzip();
+                if ids.is_empty() {
+                    open_menu(&mut commands, &asset_server, battle, unit);
+                } else {
+                    spawn_choice(
+                        &mut commands,
+                        &asset_server,
+                        "物品".into(),
+                        Choice::new(names),
+                    );
+                    battle.phase = Phase::Item(unit, ids);
+                }
+            }
+            // the turn is over
+            Some(Action::Wait) => {}
+            // Esc keeps the menu up
+            None => open_menu(&mut commands, &asset_server, battle, unit),
+        },
+        Phase::Item(unit, ids) => match picked.and_then(|v| ids.get(v)) {
+            Some(id) => {
+                battle.field.units[unit].take(&things[*id as usize]);
+                if let Some(item) = backpack.items.iter_mut().find(|(item, _)| item == id) {
+                    item.1 -= 1;
+                }
+                backpack.items.retain(|(_, num)| *num > 0);
+            }
+            None => open_menu(&mut commands, &asset_server, battle, unit),
+        },
+        phase => battle.phase = phase,
+    }
+}
+
+// moves the cursor, Return moves to or hits what is under it and Esc goes back to the menu
+fn pick_tile(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mb_battle: Option<ResMut<Battle>>,
+    people: Res<Vec<Person>>,
+    wugongs: Res<Vec<Wugong>>,
+    settings: Option<Res<Settings>>,
+    marks: Query<Entity, Marks>,
+) {
+    let mut battle = match mb_battle {
+        Some(v) => v,
+        None => return,
+    };
+    let (unit, action) = match battle.phase {
+        Phase::Target(unit, action) => (unit, action),
+        _ => return,
+    };
+    for key in [KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right] {
+        if keyboard_input.just_pressed(key) {
+            let (dx, dy) = MoveDir::from(key).unwrap().pos();
+            let (x, y) = battle.cursor;
+            battle.cursor = (
+                (x as i32 + dx).clamp(0, WAR_WIDTH as i32 - 1) as usize,
+                (y as i32 + dy).clamp(0, WAR_HEIGHT as i32 - 1) as usize,
+            );
+        }
+    }
+
+    let cursor = battle.cursor;
+    let frames = settings
+        .as_ref()
+        .and_then(|v| v.effect_frames())
+        .unwrap_or(&anim::EFFECT_FRAMES);
+    // whether the turn is over, None while picking
+    let done = if keyboard_input.just_pressed(KeyCode::Return) {
+        let me = &battle.field.units[unit];
+        let (person, pos) = (me.person, me.pos);
+        match action {
+            Action::Move if battle.field.reachable(unit).contains_key(&cursor) => {
+                battle.field.units[unit].pos = cursor;
+                battle.moved = true;
+                Some(false)
+            }
+            // the wugong of the most expected hurt that is aimed there and hits someone
+            Action::Attack => match ai::best_skill(&battle.field, unit, pos, cursor, &wugongs) {
+                Some((skill, _)) => {
+                    let person = &people[person];
+                    battle.phase =
+                        strike(&battle.field, person, &wugongs, frames, unit, cursor, skill);
+                    Some(true)
+                }
+                None => None,
+            },
+            _ => None,
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        Some(false)
+    } else {
+        None
+    };
+    let over = match done {
+        Some(v) => v,
+        None => return,
+    };
+    keyboard_input.clear();
+    for entity in marks.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !over {
+        open_menu(&mut commands, &asset_server, &mut battle, unit);
+    }
+}
+
+// an enemy plays the turn its brain plans
+fn enemy_turn(
+    time: Res<Time>,
+    mb_battle: Option<ResMut<Battle>>,
+    people: Res<Vec<Person>>,
+    wugongs: Res<Vec<Wugong>>,
+    things: Res<Vec<Thing>>,
+    settings: Option<Res<Settings>>,
+) {
+    let mut battle = match mb_battle {
+        Some(v) => v,
+        None => return,
+    };
+    let unit = match &mut battle.phase {
+        Phase::Enemy(unit, timer) => {
+            if !timer.tick(time.delta()).finished() {
+                return;
+            }
+            *unit
+        }
+        _ => return,
+    };
+    let plan = battle.brain.plan(&battle.field, unit, &wugongs, &things);
+    battle.field.units[unit].pos = plan.to;
+    battle.cursor = plan.to;
+    let frames = settings
+        .as_ref()
+        .and_then(|v| v.effect_frames())
+        .unwrap_or(&anim::EFFECT_FRAMES);
+    battle.phase = match plan.act {
+        Act::Attack(aim, skill) => {
+            let person = &people[battle.field.units[unit].person];
+            strike(&battle.field, person, &wugongs, frames, unit, aim, skill)
+        }
+        Act::Item(i) => {
+            let me = &mut battle.field.units[unit];
+            me.take(&things[me.items[i].0]);
+            me.items[i].1 -= 1;
+            me.items.retain(|(_, num)| *num > 0);
+            Phase::Next
+        }
+        Act::Wait => Phase::Next,
+    };
+}
+
+// writes the allies back and resumes the script with the victory
+fn finish(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mb_battle: Option<Res<Battle>>,
+    wars: Res<Vec<WarSta>>,
+    mut people: ResMut<Vec<Person>>,
+    mut mb_ev_script: Option<ResMut<EventScript>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let battle = match mb_battle {
+        Some(v) => v,
+        None => return,
+    };
+    let win = match battle.phase {
+        Phase::Over(v) => v,
+        _ => return,
+    };
+    if !keyboard_input.just_pressed(KeyCode::Return) && !keyboard_input.just_pressed(KeyCode::Space)
+    {
+        return;
+    }
+    keyboard_input.clear();
+
+    // the survivors share the exp
+    let allies = battle.field.units.iter().filter(|v| v.side == Side::Ally);
+    let survivors = allies.clone().filter(|v| v.is_alive()).count().max(1);
+    let exp = if win || battle.exp_on_defeat {
+        wars[battle.war].exp.max(0) as usize / survivors
+    } else {
+        0
+    };
+    // the enemies are the same records in every battle, they are left as they were
+    for unit in allies {
+        let person = &mut people[unit.person];
+        if unit.is_alive() {
+            person.add_exp(exp as u16);
+        }
+        // the fallen get up with a point of life
+        person.set_life(unit.stats.life.max(1));
+        person.set_neili(unit.stats.neili);
+        person.set_injure(unit.stats.injure);
+        person.set_tox(unit.stats.tox);
+    }
+
+    if let Some(ev_script) = mb_ev_script.as_mut() {
+        ev_script.answer = Some(Answer::Bool(win));
+        ev_script.wait_answer = false;
+        ev_script.wait_input = false;
+    }
+    commands.remove_resource::<Battle>();
+    commands.remove_resource::<FightPics>();
+    state.pop().unwrap();
+}
+
+// keeps the acting unit or the cursor in the middle and shows how it is doing
+fn update_view(
+    mb_battle: Option<Res<Battle>>,
+    people: Res<Vec<Person>>,
+    encoding: Res<TextEncoding>,
+    mut views: Query<&mut Transform, With<BattleView>>,
+    mut sprites: Query<(&UnitSprite, &mut Transform, &mut Visibility), Without<BattleView>>,
+    mut cursors: Query<&mut Transform, CursorOnly>,
+    mut hud: Query<&mut Text, With<BattleHud>>,
+) {
+    let battle = match mb_battle {
+        Some(v) => v,
+        None => return,
+    };
+    for (sprite, mut transform, mut visibility) in sprites.iter_mut() {
+        let unit = &battle.field.units[sprite.0];
+        transform.translation = tile(unit.pos, 1. + depth(unit.pos) + 0.001);
+        transform.translation.y += sprite.1;
+        visibility.is_visible = unit.is_alive() && !anim::is_swinging(&battle, sprite);
+    }
+    for mut transform in cursors.iter_mut() {
+        transform.translation = tile(battle.cursor, 0.6);
+    }
+
+    let acting = match battle.phase {
+        Phase::Menu(v, _) | Phase::Target(v, _) | Phase::Item(v, _) | Phase::Enemy(v, _) => v,
+        Phase::Fight(Fight { unit, .. }) => unit,
+        _ => return,
+    };
+    let (x, y) = battle.cursor;
+    for mut transform in views.iter_mut() {
+        transform.translation = PosXY::new(x, y).to_real(0., 0., 0.);
+    }
+    let unit = &battle.field.units[acting];
+    for mut text in hud.iter_mut() {
+        text.sections[0].value = format!(
+            "{}\n生命 {}/{}\n内力 {}/{}",
+            people[unit.person].name(*encoding),
+            unit.stats.life,
+            unit.stats.life_max,
+            unit.stats.neili,
+            unit.stats.neili_max
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! The battle formulas, after War_WugongHurtLife of the original lua.
+//!
+//! They read the records only and take the dice from the caller, so a fight can be
+//! replayed with a fixed seed and balanced without a battle on screen.
+
+use rand::Rng;
+
+use crate::game::structs::{Person, Thing, Wugong};
+
+// CC.PersonAttribMax["受伤程度"] and ["中毒程度"]
+pub const INJURE_MAX: i16 = 100;
+pub const TOX_MAX: i16 = 100;
+
+/// What the formulas read of a fighter, the record with its weapon and armor added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub life: i16,
+    pub life_max: i16,
+    pub neili: i16,
+    pub neili_max: i16,
+    pub attack: i16,
+    pub defence: i16,
+    pub agile: i16,
+    pub injure: i16,
+    pub tox: i16,
+    pub vatity: i16,
+    // 攻击带毒
+    pub with_poison: i16,
+    // 抗毒能力
+    pub poison_def: i16,
+}
+
+impl Stats {
+    pub fn new(person: &Person, weapon: Option<&Thing>, armor: Option<&Thing>) -> Self {
+        let add =
+            |f: fn(&Thing) -> i16| [weapon, armor].iter().flatten().map(|v| f(v)).sum::<i16>();
+        Stats {
+            life: person.life(),
+            life_max: person.life_max(),
+            neili: person.neili(),
+            neili_max: person.neili_max(),
+            attack: person.attack() + add(Thing::add_attack),
+            defence: person.defence() + add(Thing::add_def),
+            agile: person.agile() + add(Thing::add_agile),
+            injure: person.injure(),
+            tox: person.tox(),
+            vatity: person.vatity(),
+            with_poison: person.with_poison() + add(Thing::add_attack_poi),
+            poison_def: person.poison_def() + add(Thing::add_anti_poi),
+        }
+    }
+
+    /// With the 武器 and the 防具 of the person, looked up in the thing records.
+    pub fn of(person: &Person, things: &[Thing]) -> Self {
+        let equip = |id: i16| (id >= 0).then(|| things.get(id as usize)).flatten();
+        Stats::new(person, equip(person.weapon()), equip(person.armor()))
+    }
+}
+
+/// What a hit does, the battle applies it to the two fighters.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Hit {
+    // the level the wugong was used at, lower than asked when the 内力 is short
+    pub level: usize,
+    // 生命 the target loses
+    pub hurt: i16,
+    // 受伤程度 and 中毒程度 the target gets
+    pub injure: i16,
+    pub poison: i16,
+    // 内力 the target loses and the attacker takes, by the 杀内力 wugongs
+    pub neili_loss: i16,
+    pub neili_gain: i16,
+    // 内力 the attacker spends
+    pub neili_cost: i16,
+}
+
+// the original counts the levels from 1
+fn nth(level: usize) -> i32 {
+    level as i32 + 1
+}
+
+/// The 内力 a wugong takes at a table level, 消耗内力点数 for every two levels.
+pub fn mp_cost(wugong: &Wugong, level: usize) -> i16 {
+    ((nth(level) + 1) / 2 * wugong.mp_cost() as i32) as i16
+}
+
+/// The highest level up to `level` the 内力 is enough for, the first one anyway.
+pub fn level_in_use(wugong: &Wugong, level: usize, neili: i16) -> usize {
+    (0..=level)
+        .rev()
+        .find(|v| mp_cost(wugong, *v) <= neili)
+        .unwrap_or(0)
+}
+
+// 武功攻击力/3 and twice the 攻击力 against twice the 防御力,
+// each side weakened by half its 受伤程度 in percent
+fn base_hurt(attacker: &Stats, target: &Stats, wugong: &Wugong, level: usize) -> i32 {
+    let weaken = |v: i32, injure: i16| v * (100 - injure as i32 / 2) / 100;
+    let attack = weaken(
+        wugong.attack(level) as i32 / 3 + attacker.attack as i32 * 2,
+        attacker.injure,
+    );
+    let defence = weaken(target.defence as i32 * 2, target.injure);
+    (attack - defence) / 2
+}
+
+// the 体力 of the attacker and the wounds of the target add to the diced hurt,
+// from the tenth tile on it is two thirds
+fn hurt_at(hurt: i32, attacker: &Stats, target: &Stats, distance: usize) -> i16 {
+    let hurt = hurt + attacker.vatity as i32 / 15 + target.injure as i32 / 20;
+    let hurt = if distance < 10 {
+        hurt * (100 - (distance.max(1) as i32 - 1) * 3) / 100
+    } else {
+        hurt * 2 / 3
+    };
+    hurt.clamp(1, i16::MAX as i32) as i16
+}
+
+fn outcome(
+    attacker: &Stats,
+    target: &Stats,
+    wugong: &Wugong,
+    level: usize,
+    distance: usize,
+    diced: i32,
+) -> Hit {
+    let hurt = hurt_at(diced, attacker, target, distance);
+    // 敌人中毒点数 for every level and five for each point of 攻击带毒, 抗毒能力 keeps it off
+    let poison = nth(level) * wugong.poison() as i32 + 5 * attacker.with_poison as i32;
+    let poison = if poison > target.poison_def as i32 {
+        (poison / 15) as i16
+    } else {
+        0
+    };
+    let mut hit = Hit {
+        level,
+        poison,
+        neili_cost: mp_cost(wugong, level),
+        ..Default::default()
+    };
+    // 伤害类型 1 takes the 内力 instead of the 生命
+    if wugong.hurt_type() == 1 {
+        hit.neili_loss = (hurt + wugong.kill_mp(level)).min(target.neili.max(0));
+        hit.neili_gain = wugong.add_mp(level).clamp(0, hit.neili_loss);
+    } else {
+        hit.hurt = hurt;
+        hit.injure = hurt / 10;
+    }
+    hit
+}
+
+/// A hit of `wugong` at the table level `level` over `distance` tiles, with the dice of `rng`.
+pub fn hit<R: Rng + ?Sized>(
+    rng: &mut R,
+    attacker: &Stats,
+    target: &Stats,
+    wugong: &Wugong,
+    level: usize,
+    distance: usize,
+) -> Hit {
+    let level = level_in_use(wugong, level, attacker.neili);
+    let mut diced =
+        base_hurt(attacker, target, wugong, level) + rng.gen_range(0..20) - rng.gen_range(0..20);
+    if diced <= 0 {
+        diced = rng.gen_range(1..=10);
+    }
+    outcome(attacker, target, wugong, level, distance, diced)
+}
+
+/// The hit with the dice at their middle, to weigh the wugongs against each other.
+pub fn expected(
+    attacker: &Stats,
+    target: &Stats,
+    wugong: &Wugong,
+    level: usize,
+    distance: usize,
+) -> Hit {
+    let level = level_in_use(wugong, level, attacker.neili);
+    let base = base_hurt(attacker, target, wugong, level);
+    let diced = if base > 0 { base } else { 5 };
+    outcome(attacker, target, wugong, level, distance, diced)
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::mock::StepRng;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::game::battle::fixture::leveled;
+    use crate::game::record;
+
+    fn fighter(attack: i16, defence: i16) -> Stats {
+        Stats {
+            life: 100,
+            life_max: 100,
+            neili: 500,
+            neili_max: 500,
+            attack,
+            defence,
+            vatity: 90,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn equipped_stats() {
+        let person = record::<Person>(&[
+            ("attack", 0, 40),
+            ("defence", 0, 30),
+            ("weapon", 0, 1),
+            ("armor", 0, -1),
+        ]);
+        let sword = record::<Thing>(&[("add_attack", 0, 25), ("add_def", 0, 5)]);
+        let things = [record::<Thing>(&[]), sword];
+        let stats = Stats::of(&person, &things);
+        assert_eq!((stats.attack, stats.defence), (65, 35));
+        assert_eq!(Stats::new(&person, None, None).attack, 40);
+    }
+
+    #[test]
+    fn levels_by_neili() {
+        let w = leveled(0);
+        assert_eq!(mp_cost(&w, 0), 30);
+        assert_eq!(mp_cost(&w, 9), 150);
+        assert_eq!(level_in_use(&w, 9, 500), 9);
+        assert_eq!(level_in_use(&w, 9, 100), 5);
+        assert_eq!(level_in_use(&w, 9, 0), 0);
+    }
+
+    #[test]
+    fn hits_with_fixed_dice() {
+        let (a, t, w) = (fighter(60, 20), fighter(40, 50), leveled(0));
+        // the dice are all 0: (280/3 + 120 - 100) / 2 = 56, + 90/15 = 62
+        let hit = hit(&mut StepRng::new(0, 0), &a, &t, &w, 3, 1);
+        assert_eq!(
+            hit,
+            Hit {
+                level: 3,
+                hurt: 62,
+                injure: 6,
+                poison: 0,
+                neili_loss: 0,
+                neili_gain: 0,
+                neili_cost: 60,
+            }
+        );
+        assert_eq!(expected(&a, &t, &w, 3, 1), hit);
+        // three percent less for every tile
+        assert_eq!(expected(&a, &t, &w, 3, 3).hurt, 58);
+        assert_eq!(expected(&a, &t, &w, 3, 12).hurt, 41);
+
+        // too weak to get through, a die of 1 - 10
+        let hit = super::hit(&mut StepRng::new(0, 0), &fighter(0, 0), &t, &w, 0, 1);
+        assert_eq!(hit.hurt, 7);
+    }
+
+    #[test]
+    fn poison_and_neili() {
+        let (mut a, mut t) = (fighter(60, 20), fighter(40, 50));
+        a.with_poison = 20;
+        // 4 * 2 + 5 * 20 = 108 against no 抗毒能力
+        assert_eq!(expected(&a, &t, &leveled(0), 3, 1).poison, 7);
+        t.poison_def = 108;
+        assert_eq!(expected(&a, &t, &leveled(0), 3, 1).poison, 0);
+
+        t.neili = 50;
+        let hit = expected(&a, &t, &leveled(1), 3, 1);
+        assert_eq!((hit.hurt, hit.neili_loss, hit.neili_gain), (0, 50, 10));
+    }
+
+    #[test]
+    fn seeded_fights() {
+        let (a, t, w) = (fighter(60, 20), fighter(40, 50), leveled(0));
+        let fight = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..100)
+                .map(|_| hit(&mut rng, &a, &t, &w, 3, 1).hurt)
+                .collect::<Vec<_>>()
+        };
+        let hurts = fight(7);
+        assert_eq!(hurts, fight(7));
+        // 56 +- 19 before the 体力
+        assert!(hurts.iter().all(|v| (43..=81).contains(v)), "{:?}", hurts);
+        let mean = hurts.iter().map(|v| *v as i32).sum::<i32>() / hurts.len() as i32;
+        assert!((56..=68).contains(&mean), "{}", mean);
+    }
+}
@@ -0,0 +1,138 @@
+use std::str::FromStr;
+
+use bevy::prelude::{FromWorld, World};
+use encoding_rs::{Encoding, BIG5, GBK, UTF_8};
+
+use crate::settings::Settings;
+
+/// The code page of the text in the data files, the original release is big5,
+/// the simplified one is gbk and the converted data sets are utf-8.
+/// A resource of the world, taken from `Settings`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextEncoding {
+    Big5,
+    Gbk,
+    Utf8,
+}
+
+impl TextEncoding {
+    fn codec(&self) -> &'static Encoding {
+        match self {
+            TextEncoding::Big5 => BIG5,
+            TextEncoding::Gbk => GBK,
+            TextEncoding::Utf8 => UTF_8,
+        }
+    }
+}
+
+impl FromWorld for TextEncoding {
+    fn from_world(world: &mut World) -> Self {
+        world
+            .get_resource::<Settings>()
+            .map_or(TextEncoding::Big5, |v| v.encoding())
+    }
+}
+
+impl FromStr for TextEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "big5" => Ok(TextEncoding::Big5),
+            "gbk" | "gb2312" | "gb18030" => Ok(TextEncoding::Gbk),
+            "utf-8" | "utf8" => Ok(TextEncoding::Utf8),
+            _ => Err(format!("unknown encoding {}", s)),
+        }
+    }
+}
+
+/// Decodes a zero terminated string of the data files with the configured encoding.
+pub fn decode(encoding: TextEncoding, v: &[u8]) -> String {
+    let v = v.split(|v| *v == 0).next().unwrap_or_default();
+    // many big5 and gbk pairs are valid utf-8 too, the configured one goes first and
+    // utf-8 only reads the text it can not
+    let fallbacks = [
+        encoding,
+        TextEncoding::Utf8,
+        TextEncoding::Big5,
+        TextEncoding::Gbk,
+    ];
+    fallbacks
+        .iter()
+        .find_map(|e| {
+            e.codec()
+                .decode_without_bom_handling_and_without_replacement(v)
+        })
+        .map(|s| s.into_owned())
+        .unwrap_or_else(|| {
+            encoding
+                .codec()
+                .decode_without_bom_handling(v)
+                .0
+                .into_owned()
+        })
+}
+
+/// Encodes into a fixed-length zero padded field, a character that does not fit is dropped whole.
+pub fn encode_into(encoding: TextEncoding, s: &str, out: &mut [u8]) {
+    out.fill(0);
+    let mut len = 0;
+    let mut buf = [0; 4];
+    for c in s.chars() {
+        let (bytes, _, unmappable) = encoding.codec().encode(c.encode_utf8(&mut buf));
+        // the encoder would write an html escape instead
+        let bytes: &[u8] = if unmappable { b"?" } else { &bytes };
+        if len + bytes.len() > out.len() {
+            break;
+        }
+        out[len..len + bytes.len()].copy_from_slice(bytes);
+        len += bytes.len();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn legacy_round_trip() {
+        for encoding in [TextEncoding::Big5, TextEncoding::Gbk, TextEncoding::Utf8] {
+            let mut name = [0; 20];
+            encode_into(encoding, "胡斐", &mut name);
+            assert_eq!(decode(encoding, &name), "胡斐");
+
+            let raw = name;
+            encode_into(encoding, &decode(encoding, &raw), &mut name);
+            assert_eq!(name, raw);
+        }
+    }
+
+    #[test]
+    fn known_bytes() {
+        // 金庸
+        assert_eq!(
+            decode(TextEncoding::Big5, &[0xaa, 0xf7, 0xb1, 0x65, 0]),
+            "金庸"
+        );
+        assert_eq!(
+            decode(TextEncoding::Gbk, &[0xbd, 0xf0, 0xd3, 0xb9, 0]),
+            "金庸"
+        );
+        assert_eq!(decode(TextEncoding::Big5, "金庸".as_bytes()), "金庸");
+    }
+
+    #[test]
+    fn legacy_before_utf8() {
+        // 羅瓊 in big5 is ùã in utf-8
+        let name = [0xc3, 0xb9, 0xc3, 0xa3, 0];
+        assert_eq!(decode(TextEncoding::Big5, &name), "羅瓊");
+        assert_eq!(decode(TextEncoding::Utf8, &name), "ùã");
+    }
+
+    #[test]
+    fn truncate_on_char() {
+        let mut out = [0xff; 5];
+        encode_into(TextEncoding::Gbk, "金庸群", &mut out);
+        assert_eq!(out, [0xbd, 0xf0, 0xd3, 0xb9, 0]);
+    }
+}
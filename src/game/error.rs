@@ -0,0 +1,136 @@
+use std::fmt;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use crate::game::util::despawn_screen;
+use crate::game::GameState;
+
+/// What can be wrong with the original data files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JyDataError {
+    // an idx entry past the end of the idx file
+    NoEntry {
+        entry: usize,
+        len: usize,
+    },
+    // an idx entry pointing backwards or past the end of the grp data
+    BadEntry {
+        entry: usize,
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+    // less bytes than the layout needs
+    Truncated {
+        what: &'static str,
+        need: usize,
+        got: usize,
+    },
+    // an index outside of a loaded table
+    OutOfRange {
+        what: &'static str,
+        index: usize,
+        len: usize,
+    },
+    // bytes that do not decode
+    Corrupt {
+        what: &'static str,
+        index: usize,
+    },
+    // a file the asset server failed to load
+    Missing(String),
+}
+
+impl fmt::Display for JyDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JyDataError::NoEntry { entry, len } => {
+                write!(f, "idx entry {} does not exist, there are {}", entry, len)
+            }
+            JyDataError::BadEntry {
+                entry,
+                start,
+                end,
+                len,
+            } => write!(
+                f,
+                "idx entry {} points to {}..{} of a {} bytes grp",
+                entry, start, end, len
+            ),
+            JyDataError::Truncated { what, need, got } => {
+                write!(f, "{} needs {} bytes, got {}", what, need, got)
+            }
+            JyDataError::OutOfRange { what, index, len } => {
+                write!(f, "{} {} is out of range, there are {}", what, index, len)
+            }
+            JyDataError::Corrupt { what, index } => write!(f, "{} {} is corrupt", what, index),
+            JyDataError::Missing(path) => write!(f, "failed to load {}", path),
+        }
+    }
+}
+
+impl std::error::Error for JyDataError {}
+
+/// The errors found by `load`, shown instead of starting the game.
+pub struct DataErrors(pub Vec<JyDataError>);
+
+pub struct Plugin;
+
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::DataError).with_system(setup))
+            .add_system_set(SystemSet::on_update(GameState::DataError).with_system(keyboard_input))
+            .add_system_set(
+                SystemSet::on_exit(GameState::DataError).with_system(despawn_screen::<ErrorScreen>),
+            );
+    }
+}
+
+#[derive(Component)]
+struct ErrorScreen;
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>, errors: Res<DataErrors>) {
+    let font = asset_server.load("fonts/simsun.ttf");
+    let style = TextStyle {
+        font,
+        font_size: 24.0,
+        color: Color::WHITE,
+    };
+    for e in errors.0.iter() {
+        error!("{}", e);
+    }
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexStart,
+                justify_content: JustifyContent::Center,
+                padding: Rect::all(Val::Px(40.)),
+                ..Default::default()
+            },
+            color: Color::BLACK.into(),
+            ..Default::default()
+        })
+        .insert(ErrorScreen)
+        .with_children(|p| {
+            let lines = std::iter::once("数据文件损坏 (the data files are broken):".to_string())
+                .chain(errors.0.iter().map(|v| v.to_string()))
+                .chain(std::iter::once("Esc".to_string()));
+            for line in lines {
+                p.spawn_bundle(TextBundle {
+                    text: Text::with_section(line, style.clone(), Default::default()),
+                    ..Default::default()
+                });
+            }
+        });
+}
+
+fn keyboard_input(keyboard_input: Res<Input<KeyCode>>, mut exit: EventWriter<AppExit>) {
+    if keyboard_input.just_pressed(KeyCode::Escape) || keyboard_input.just_pressed(KeyCode::Return)
+    {
+        exit.send(AppExit);
+    }
+}
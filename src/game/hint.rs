@@ -84,7 +84,8 @@ fn update_hint_box(
                     sta.pos.x as usize,
                     sta.pos.y as usize,
                     1,
-                );
+                )
+                .unwrap_or(0);
                 if pic > 0 {
                     mb_meta = image_cache.get_image(MapType::Smap, pic as usize / 2).map(|v| v.1.clone());
                 }
@@ -0,0 +1,224 @@
+use crate::game::Menu;
+
+use super::*;
+
+const YES_NO: [&str; 2] = ["是", "否"];
+
+/// The choice box of the running script, Up / Down and Return pick an option like the splash menu.
+#[derive(Debug, Clone)]
+pub struct Choice {
+    options: Vec<String>,
+    idx: usize,
+    // answers a bool instead of the option, Y / N pick right away
+    yes_no: bool,
+}
+
+impl Choice {
+    pub fn new(options: Vec<String>) -> Self {
+        Choice {
+            options,
+            idx: 0,
+            yes_no: false,
+        }
+    }
+
+    /// 是 / 否, the answer is true for 是.
+    pub fn yes_no() -> Self {
+        Choice {
+            yes_no: true,
+            ..Choice::new(YES_NO.iter().map(|v| v.to_string()).collect())
+        }
+    }
+
+    /// Moves with Up / Down and colors the rows, returns the pick once it is made,
+    /// None for Esc, or N to a yes / no question.
+    pub fn update(
+        &self,
+        commands: &mut Commands,
+        keyboard_input: &mut Input<KeyCode>,
+        rows: &mut Query<(&ChoiceRow, &mut Text)>,
+    ) -> Option<Option<usize>> {
+        for (row, mut text) in rows.iter_mut() {
+            text.sections[0].style.color = if row.0 == self.to_idx() {
+                Color::WHITE
+            } else {
+                Color::GRAY
+            };
+        }
+
+        if keyboard_input.just_pressed(KeyCode::Up) {
+            commands.insert_resource(self.up());
+            keyboard_input.clear();
+            return None;
+        }
+        if keyboard_input.just_pressed(KeyCode::Down) {
+            commands.insert_resource(self.down());
+            keyboard_input.clear();
+            return None;
+        }
+        let picked = if keyboard_input.just_pressed(KeyCode::Return) {
+            Some(self.to_idx())
+        } else if self.yes_no && keyboard_input.just_pressed(KeyCode::Y) {
+            Some(0)
+        } else if (self.yes_no && keyboard_input.just_pressed(KeyCode::N))
+            || keyboard_input.just_pressed(KeyCode::Escape)
+        {
+            None
+        } else {
+            return None;
+        };
+        keyboard_input.clear();
+        commands.remove_resource::<Choice>();
+        Some(picked)
+    }
+
+    fn answer(&self, picked: Option<usize>) -> Answer {
+        if self.yes_no {
+            Answer::Bool(picked == Some(0))
+        } else {
+            Answer::Choice(picked)
+        }
+    }
+}
+
+impl Menu for Choice {
+    fn up(&self) -> Self {
+        Choice {
+            idx: self.idx.saturating_sub(1),
+            ..self.clone()
+        }
+    }
+
+    fn down(&self) -> Self {
+        Choice {
+            idx: (self.idx + 1).min(self.options.len().saturating_sub(1)),
+            ..self.clone()
+        }
+    }
+
+    fn to_name(&self) -> String {
+        self.options.get(self.idx).cloned().unwrap_or_default()
+    }
+
+    fn to_idx(&self) -> usize {
+        self.idx
+    }
+
+    fn i18n(&self) -> Option<i32> {
+        None
+    }
+}
+
+#[derive(Component)]
+pub struct ChoiceRow(usize);
+
+/// Shows the questions of instruct_5, 9 and 11 and the option lists of the scripts,
+/// `choice_menu` answers them.
+pub fn handle_choice(
+    mut commands: Commands,
+    mut mb_ev_script: Option<ResMut<EventScript>>,
+    asset_server: Res<AssetServer>,
+) {
+    if mb_ev_script.is_none() {
+        return;
+    }
+
+    let ev_script = mb_ev_script.as_mut().unwrap();
+    let (title, choice) = match ev_script.dispatch.as_ref() {
+        Some(JyEvent::Ask(question)) => (question.clone(), Choice::yes_no()),
+        Some(JyEvent::Choose(title, options)) => (title.clone(), Choice::new(options.clone())),
+        _ => return,
+    };
+    spawn_choice(&mut commands, &asset_server, title, choice);
+    ev_script.wait_input = true;
+    ev_script.wait_answer = true;
+    ev_script.dispatch.take();
+}
+
+/// Shows a choice box with `title` above the options and makes `choice` the resource that runs it.
+pub fn spawn_choice(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    title: String,
+    choice: Choice,
+) {
+    let style = |color| TextStyle {
+        font: asset_server.load("fonts/simsun.ttf"),
+        font_size: 32.0,
+        color,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(DialogBox)
+        .with_children(|p| {
+            p.spawn_bundle(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::ColumnReverse,
+                    align_items: AlignItems::Center,
+                    padding: Rect::all(Val::Px(10.)),
+                    ..Default::default()
+                },
+                color: Color::rgba(0., 0., 0., 0.85).into(),
+                ..Default::default()
+            })
+            .with_children(|p| {
+                p.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(5.)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(title, style(Color::ORANGE), Default::default()),
+                    ..Default::default()
+                });
+                for (n, option) in choice.options.iter().enumerate() {
+                    let color = if n == choice.idx {
+                        Color::WHITE
+                    } else {
+                        Color::GRAY
+                    };
+                    p.spawn_bundle(TextBundle {
+                        text: Text::with_section(option.clone(), style(color), Default::default()),
+                        ..Default::default()
+                    })
+                    .insert(ChoiceRow(n));
+                }
+            });
+        });
+    commands.insert_resource(choice);
+}
+
+/// Moves through the shown choice and hands the picked option to the script,
+/// Esc cancels it, which is 否 for a yes / no question.
+pub fn choice_menu(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut mb_ev_script: Option<ResMut<EventScript>>,
+    mb_choice: Option<Res<Choice>>,
+    mut rows: Query<(&ChoiceRow, &mut Text)>,
+    boxes: Query<Entity, With<DialogBox>>,
+) {
+    let (ev_script, choice) = match (mb_ev_script.as_mut(), mb_choice) {
+        (Some(ev_script), Some(choice)) if ev_script.wait_answer => (ev_script, choice),
+        _ => return,
+    };
+    let picked = match choice.update(&mut commands, &mut keyboard_input, &mut rows) {
+        Some(v) => v,
+        None => return,
+    };
+    for entity in boxes.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    ev_script.answer = Some(choice.answer(picked));
+    ev_script.wait_answer = false;
+    ev_script.wait_input = false;
+}
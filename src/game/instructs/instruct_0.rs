@@ -1,5 +1,6 @@
 use super::*;
 
+#[jy_instruct(0, handler = handle_instruct_0)]
 pub fn instruct_0() -> JyEvent {
     JyEvent::Cls
 }
@@ -1,42 +1,167 @@
 use super::*;
+use crate::game::encoding::TextEncoding;
+use crate::game::util::ImageCache;
 
-
+#[jy_instruct(1, handler = handle_instruct_1)]
 pub fn instruct_1(talk_id: i32, head_id: i32, flag: i32) -> JyEvent {
-    JyEvent::Dialog("Hello world".into())
+    JyEvent::Talk(talk_id, head_id, flag)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum HeadSide {
+    Left,
+    Right,
+    // no portrait, the text keeps its side
+    None,
+}
+
+/// The placement codes of the original TalkEx
+/// 0 屏幕上方显示, 左边头像，右边对话
+/// 1 屏幕下方显示, 左边对话，右边头像
+/// 2 屏幕上方显示, 左边空，右边对话
+/// 3 屏幕下方显示, 左边对话，右边空
+/// 4 屏幕上方显示, 左边对话，右边头像
+/// 5 屏幕下方显示, 左边头像，右边对话
+fn dialog_layout(flag: i32) -> (bool, HeadSide) {
+    match flag {
+        0 => (true, HeadSide::Left),
+        1 => (false, HeadSide::Right),
+        2 => (true, HeadSide::None),
+        3 => (false, HeadSide::None),
+        4 => (true, HeadSide::Right),
+        _ => (false, HeadSide::Left),
+    }
 }
 
+// the portrait column, in percent of the box
+const HEAD_WIDTH: f32 = 20.;
+
 pub fn handle_instruct_1(
-        mut commands: Commands,
-        mut mb_ev_script: Option<ResMut<EventScript>>,
-        asset_server: Res<AssetServer>,
-        ) {
+    mut commands: Commands,
+    mut mb_ev_script: Option<ResMut<EventScript>>,
+    asset_server: Res<AssetServer>,
+    talks: Res<Talks>,
+    encoding: Res<TextEncoding>,
+    // absent in a headless world
+    mut image_cache: Option<ResMut<ImageCache>>,
+    query: Query<Entity, With<DialogBox>>,
+) {
     if mb_ev_script.is_none() {
         return;
     }
 
     let ev_script = mb_ev_script.as_mut().unwrap();
-    if let Some(JyEvent::Dialog(v)) = ev_script.dispatch.as_ref() {
-        commands
-        .spawn_bundle(TextBundle {
+    let (text, head_id, flag) = match ev_script.dispatch.as_ref() {
+        Some(JyEvent::Dialog(v)) => (v.clone(), -1, 5),
+        Some(JyEvent::Talk(talk_id, head_id, flag)) => match talks.get(*talk_id as usize, *encoding) {
+            Ok(v) => (v, *head_id, *flag),
+            Err(e) => {
+                warn!("no talk {}: {}", talk_id, e);
+                (String::new(), *head_id, *flag)
+            }
+        },
+        _ => return,
+    };
+
+    // one box at a time, like the original
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let (top, side) = dialog_layout(flag);
+    let head = match image_cache.as_mut() {
+        Some(image_cache) if head_id >= 0 => image_cache
+            .get_image(MapType::Head, head_id as usize)
+            .map(|v| v.0),
+        _ => None,
+    };
+    let vertical = if top {
+        Rect {
+            top: Val::Percent(2.),
+            ..Default::default()
+        }
+    } else {
+        Rect {
+            bottom: Val::Percent(2.),
+            ..Default::default()
+        }
+    };
+    let head_slot = |p: &mut ChildBuilder| {
+        let style = Style {
+            size: Size::new(Val::Percent(HEAD_WIDTH), Val::Percent(100.)),
+            ..Default::default()
+        };
+        match head.clone() {
+            Some(image) => p.spawn_bundle(ImageBundle {
+                style,
+                image: image.into(),
+                ..Default::default()
+            }),
+            None => p.spawn_bundle(NodeBundle {
+                style,
+                color: Color::NONE.into(),
+                ..Default::default()
+            }),
+        };
+    };
+
+    commands
+        // the frame
+        .spawn_bundle(NodeBundle {
             style: Style {
-                flex_direction: FlexDirection::ColumnReverse,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(5.),
+                    ..vertical
+                },
+                size: Size::new(Val::Percent(90.), Val::Percent(28.)),
+                padding: Rect::all(Val::Px(3.)),
                 ..Default::default()
             },
-            text: Text {
-                sections: vec![TextSection {
-                    style: TextStyle {
-                        font: asset_server.load("fonts/simsun.ttf"),
-                        font_size: 60.0,
-                        color: Color::WHITE,
-                    },
-                    value: v.to_string(),
-                }],
-                alignment: Default::default(),
-            },
+            color: Color::rgb(0.8, 0.7, 0.5).into(),
             ..Default::default()
         })
-        .insert(DialogBox);
-        ev_script.wait_input = true;
-        ev_script.dispatch.take();
-    }
-}
\ No newline at end of file
+        .insert(DialogBox)
+        .with_children(|p| {
+            p.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    padding: Rect::all(Val::Px(10.)),
+                    ..Default::default()
+                },
+                color: Color::rgba(0., 0., 0., 0.85).into(),
+                ..Default::default()
+            })
+            .with_children(|p| {
+                if side == HeadSide::Left {
+                    head_slot(p);
+                }
+                p.spawn_bundle(TextBundle {
+                    style: Style {
+                        flex_grow: 1.,
+                        flex_shrink: 1.,
+                        max_size: Size::new(Val::Percent(100. - HEAD_WIDTH), Val::Undefined),
+                        margin: Rect::all(Val::Px(10.)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        text,
+                        TextStyle {
+                            font: asset_server.load("fonts/simsun.ttf"),
+                            font_size: 32.0,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+                if side == HeadSide::Right {
+                    head_slot(p);
+                }
+            });
+        });
+    ev_script.wait_input = true;
+    ev_script.dispatch.take();
+}
@@ -0,0 +1,9 @@
+use super::*;
+
+// function instruct_11()
+//     return DrawStrBoxYesNo(-1,-1,"是否住宿(Y/N)?",C_ORANGE,CC.DefaultFont);
+// end
+#[jy_instruct(11, yields)]
+pub fn instruct_11() -> JyEvent {
+    JyEvent::Ask("是否住宿(Y/N)?".into())
+}
@@ -0,0 +1,23 @@
+use super::*;
+
+#[jy_instruct(18, handler = handle_instruct_18, yields)]
+pub fn instruct_18(thing: i16) -> JyEvent {
+    JyEvent::HasItem(thing)
+}
+
+// function instruct_18(thingid)
+//     for i = 1,CC.MyThingNum do
+//         if JY.Base["物品" .. i]==thingid then
+//             return true;
+pub fn handle_instruct_18(backpack: Res<Backpack>, mut mb_ev_script: Option<ResMut<EventScript>>) {
+    if mb_ev_script.is_none() {
+        return;
+    }
+    let ev_script = mb_ev_script.as_mut().unwrap();
+    if let Some(&JyEvent::HasItem(thing)) = ev_script.dispatch.as_ref() {
+        ev_script.answer = Some(Answer::Bool(
+            backpack.items.iter().any(|(item, _)| *item == thing),
+        ));
+        ev_script.dispatch.take();
+    }
+}
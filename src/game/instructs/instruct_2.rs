@@ -1,7 +1,9 @@
 
 
 use super::*;
+use crate::game::encoding::TextEncoding;
 
+#[jy_instruct(2, handler = handle_instruct_2)]
 pub fn instruct_2(thing: i16, num: i16) -> JyEvent {
     JyEvent::Instruct2(thing, num)
 }
@@ -9,6 +11,7 @@ pub fn instruct_2(thing: i16, num: i16) -> JyEvent {
 pub fn handle_instruct_2(
         d_data: ResMut<DData>,
         things: Res<Vec<Thing>>,
+        encoding: Res<TextEncoding>,
         mut backpack: ResMut<Backpack>,
         mut mb_ev_script: Option<ResMut<EventScript>>,
         sta: Res<SceneStatus>,
@@ -18,29 +21,10 @@ pub fn handle_instruct_2(
     }
     let ev_script = mb_ev_script.as_mut().unwrap();
     if let Some(&JyEvent::Instruct2(thing, size)) = ev_script.dispatch.as_ref() {
-        add_to_backpack(&mut backpack, thing, size);
+        backpack.add(thing, size);
         ev_script.dispatch.take();
-        let output = format!("得到物品:{} {}", things[thing as usize].name(), size);
+        let output = format!("得到物品:{} {}", things[thing as usize].name(*encoding), size);
         ev_script.events.push(JyEvent::Dialog(output));
         ev_script.events.push(JyEvent::Cls);
     }
 }
-
-fn add_to_backpack(backpack: &mut ResMut<Backpack>, thing: i16, size: i16) {
-    match backpack
-    .items
-    .iter_mut()
-    .enumerate()
-    .find(|(_, (item, _))| *item == thing)
-    {
-        None => {
-            backpack.items.push((thing, size));
-        }
-        Some((idx, (item, cur))) => {
-            *cur += size;
-            if *cur == 0 {
-                backpack.items.remove(idx);
-            }
-        }
-    }
-}
\ No newline at end of file
@@ -1,33 +1,134 @@
-use crate::game::{smap::{Me, SMapScreen}, mmap::ImageCache, util::RenderHelper};
+use crate::game::smap::{self, JyBox, Me, SMapScreen};
+use crate::game::util::{ImageCache, RenderHelper};
 
 use super::*;
 
+// the delay between two frames
+const FRAME_SECS: f32 = 0.05;
+
+#[jy_instruct(27, handler = handle_instruct_27)]
 pub fn instruct_27(id: i16, start_pic: i16, end_pic: i16) -> JyEvent {
-    JyEvent::Sprite
+    JyEvent::Animate(id, start_pic, end_pic)
+}
+
+/// A playing `instruct_27`, the script waits for its last frame.
+pub struct Animation {
+    // the event in the current scene, None is the player
+    target: Option<usize>,
+    pics: Vec<i16>,
+    next: usize,
+    // D* fields 5, 6 and 7 of the event, restored at the end
+    saved: Vec<i16>,
+    timer: Timer,
+}
+
+impl Animation {
+    fn new(id: i16, start_pic: i16, end_pic: i16, d_data: &DData, cur_s: usize) -> Self {
+        let target = (id >= 0).then_some(id as usize);
+        let saved = target.map_or(vec![], |id| {
+            (5..=7)
+                .map(|i| d_data.get_d(cur_s, id, i).unwrap_or(0))
+                .collect()
+        });
+        Animation {
+            target,
+            // the pictures are numbered by two, like the D* ones
+            pics: (start_pic..=end_pic).step_by(2).collect(),
+            next: 0,
+            saved,
+            timer: Timer::from_seconds(FRAME_SECS, true),
+        }
+    }
 }
 
 pub fn handle_instruct_27(
         mut commands: Commands,
         mb_ev_script: Option<ResMut<EventScript>>,
-        query: Query<Entity, With<Me>>,
-        asset_server: Res<AssetServer>,
-        sta: ResMut<SceneStatus>,
-        image_cache: ResMut<ImageCache>,
+        mut animation: Local<Option<Animation>>,
+        time: Res<Time>,
+        mut d_data: ResMut<DData>,
+        mut sta: ResMut<SceneStatus>,
+        s_data: Res<SData>,
+        me_query: Query<Entity, With<Me>>,
+        boxes: Query<&JyBox>,
+        // absent in a headless world, the frames step once per update there
+        mut image_cache: Option<ResMut<ImageCache>>,
+        mut render_helper: Option<ResMut<RenderHelper>>,
         mut images: ResMut<Assets<Image>>,
-        mut render_helper: ResMut<RenderHelper>,
-        textures: ResMut<Assets<TextureAtlas>>,
         ) {
     if mb_ev_script.is_none() {
         return;
     }
 
     let mut ev_script = mb_ev_script.unwrap();
-    if let Some(JyEvent::Sprite) = ev_script.dispatch.as_ref() {
-        for entity in query.iter() {
-            commands.entity(entity).despawn_recursive();
-        }
-        let entity = render_helper.render_sprite(&mut commands, MapType::Smap, &mut images);
-        commands.entity(entity).insert(Me).insert(SMapScreen);
+    if let Some(&JyEvent::Animate(id, start_pic, end_pic)) = ev_script.dispatch.as_ref() {
+        *animation = Some(Animation::new(id, start_pic, end_pic, &d_data, sta.cur_s));
+        ev_script.animating = true;
         ev_script.dispatch.take();
     }
-}
\ No newline at end of file
+
+    let anim = match animation.as_mut() {
+        Some(v) => v,
+        None => return,
+    };
+    // the first frame is shown right away
+    if render_helper.is_some() && anim.next > 0 && !anim.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let cur_s = sta.cur_s;
+    let (pic, fields) = match (anim.pics.get(anim.next), anim.target) {
+        (Some(pic), _) => (*pic, vec![*pic; 3]),
+        // back to how it was
+        (None, Some(_)) => (anim.saved[2], anim.saved.clone()),
+        (None, None) => (-1, vec![]),
+    };
+    match anim.target {
+        Some(id) => {
+            for (i, v) in fields.iter().enumerate() {
+                if let Err(e) = d_data.set(cur_s, id, 5 + i, *v) {
+                    warn!("instruct_27 failed to set D*: {}", e);
+                }
+            }
+            if let Some(render_helper) = render_helper.as_mut() {
+                let x = d_data.get_d(cur_s, id, 9).unwrap_or(0) as usize;
+                let y = d_data.get_d(cur_s, id, 10).unwrap_or(0) as usize;
+                smap::redraw_event(
+                    &mut commands,
+                    render_helper,
+                    &s_data,
+                    &sta,
+                    &boxes,
+                    (x, y),
+                    (pic / 2).max(0) as usize,
+                );
+            }
+        }
+        None if pic >= 0 => {
+            sta.cur_pic = pic as usize / 2;
+            if let Some(image_cache) = image_cache.as_mut() {
+                for entity in me_query.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                smap::spawn_me_pic(&mut commands, image_cache, &s_data, &sta, sta.cur_pic);
+            }
+        }
+        // the player walks again
+        None => {
+            if let Some(render_helper) = render_helper.as_mut() {
+                for entity in me_query.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                let entity = render_helper.render_sprite(&mut commands, MapType::Smap, &mut images);
+                commands.entity(entity).insert(Me).insert(SMapScreen);
+            }
+        }
+    }
+
+    if anim.next == anim.pics.len() {
+        *animation = None;
+        ev_script.animating = false;
+    } else {
+        anim.next += 1;
+    }
+}
@@ -2,6 +2,7 @@ use crate::game::{util::RenderHelper, smap::{JyBox, SMapScreen}};
 
 use super::*;
 
+#[jy_instruct(3, handler = handle_instruct_3)]
 pub fn instruct_3(
         s: i16,
         d: i16,
@@ -36,7 +37,7 @@ pub fn handle_instruct_3(
         mut d_data: ResMut<DData>,
         mut mb_ev_script: Option<ResMut<EventScript>>,
         sta: ResMut<SceneStatus>,
-        mut render_helper: ResMut<RenderHelper>,
+        mut render_helper: Option<ResMut<RenderHelper>>,
         s_data: Res<SData>,
         query: Query<(&Transform, &JyBox), With<JyBox>>,
         ) {
@@ -58,13 +59,16 @@ pub fn handle_instruct_3(
 
         let mut image_update = None;
         for (k, v) in vals {
-            d_data.set(s as usize, dv, *k, *v);
+            if let Err(e) = d_data.set(s as usize, dv, *k, *v) {
+                warn!("instruct_3 failed to set D*: {}", e);
+            }
             if *k == 7 {
                 image_update = Some(*v);
             }
         }
 
-        if image_update.is_some() {
+        // no rendering in a headless world
+        if let (Some(render_helper), Some(_)) = (render_helper.as_mut(), image_update) {
             for (tran, bx) in query.iter() {
                 if bx.1 == sta.cur_d.1 && bx.2 == sta.cur_d.2 {
                     let x = bx.1 as f32;
@@ -72,7 +76,7 @@ pub fn handle_instruct_3(
                     commands.entity(bx.0).despawn_recursive();
                     let mut trans = Transform::from_translation(sta.pos.to_real(x, y, 3.));
                     trans.translation.y +=
-                    s_data.get_texture(sta.cur_s, x as usize, y as usize, 4) as f32;
+                    s_data.get_texture(sta.cur_s, x as usize, y as usize, 4).unwrap_or(0) as f32;
                     render_helper
                     .render(
                             &mut commands,
@@ -0,0 +1,9 @@
+use super::*;
+
+// function instruct_5()
+//     return DrawStrBoxYesNo(-1,-1,"是否与之过招(Y/N)?",C_ORANGE,CC.DefaultFont);
+// end
+#[jy_instruct(5, yields)]
+pub fn instruct_5() -> JyEvent {
+    JyEvent::Ask("是否与之过招(Y/N)?".into())
+}
@@ -0,0 +1,41 @@
+use crate::game::battle::BattleRequest;
+use crate::game::GameState;
+
+use super::*;
+
+// function instruct_6(warid,tmp,tmp,flag)
+//     return WarMain(warid,flag);
+// end
+#[jy_instruct(6, handler = handle_instruct_6, yields)]
+pub fn instruct_6(war: i16, _yes: i16, _no: i16, flag: i16) -> JyEvent {
+    JyEvent::Battle(war, flag)
+}
+
+/// Starts the battle on top of the interaction, the script waits for the victory.
+pub fn handle_instruct_6(
+    mut commands: Commands,
+    mut mb_ev_script: Option<ResMut<EventScript>>,
+    wars: Option<Res<Vec<WarSta>>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if mb_ev_script.is_none() {
+        return;
+    }
+    let ev_script = mb_ev_script.as_mut().unwrap();
+    if let Some(&JyEvent::Battle(war, flag)) = ev_script.dispatch.as_ref() {
+        ev_script.dispatch.take();
+        if war < 0 || wars.as_ref().and_then(|v| v.get(war as usize)).is_none() {
+            // lost, as the original does with a battle it can not load
+            warn!("there is no battle {} in war.sta", war);
+            ev_script.answer = Some(Answer::Bool(false));
+            return;
+        }
+        commands.insert_resource(BattleRequest {
+            war: war as usize,
+            exp_on_defeat: flag != 0,
+        });
+        ev_script.wait_input = true;
+        ev_script.wait_answer = true;
+        state.push(GameState::Battle).unwrap();
+    }
+}
@@ -0,0 +1,9 @@
+use super::*;
+
+// function instruct_9()
+//     return DrawStrBoxYesNo(-1,-1,"是否要求加入(Y/N)?",C_ORANGE,CC.DefaultFont);
+// end
+#[jy_instruct(9, yields)]
+pub fn instruct_9() -> JyEvent {
+    JyEvent::Ask("是否要求加入(Y/N)?".into())
+}
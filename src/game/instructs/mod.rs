@@ -1,33 +1,178 @@
+mod choice;
+pub use choice::{choice_menu, handle_choice, spawn_choice, Choice, ChoiceRow};
+
 mod instruct_0;
 use bevy::prelude::*;
-pub use instruct_0::{instruct_0, handle_instruct_0};
+pub use instruct_0::{instruct_0, handle_instruct_0, Instruct0};
 
 mod instruct_1;
-pub use instruct_1::{instruct_1, handle_instruct_1};
+pub use instruct_1::{instruct_1, handle_instruct_1, Instruct1};
 
 mod instruct_2;
-pub use instruct_2::{instruct_2, handle_instruct_2};
+pub use instruct_2::{instruct_2, handle_instruct_2, Instruct2};
 
 mod instruct_3;
-pub use instruct_3::{instruct_3, handle_instruct_3};
+pub use instruct_3::{instruct_3, handle_instruct_3, Instruct3};
+
+mod instruct_5;
+pub use instruct_5::{instruct_5, Instruct5};
+
+mod instruct_6;
+pub use instruct_6::{instruct_6, handle_instruct_6, Instruct6};
+
+mod instruct_9;
+pub use instruct_9::{instruct_9, Instruct9};
+
+mod instruct_11;
+pub use instruct_11::{instruct_11, Instruct11};
+
+mod instruct_18;
+pub use instruct_18::{instruct_18, handle_instruct_18, Instruct18};
 
 mod instruct_27;
-pub use instruct_27::{instruct_27, handle_instruct_27};
+pub use instruct_27::{instruct_27, handle_instruct_27, Instruct27};
 
 use crate::game::structs::*;
+use jy_derive::jy_instruct;
+use std::sync::{Arc, Mutex};
+
+/// Where the lua bindings queue the events of the running script.
+pub type EventQueue = Arc<Mutex<Vec<JyEvent>>>;
+
+/// A script instruction, implemented by `#[jy_instruct(N)]` on its `instruct_N` constructor.
+pub trait Instruct {
+    const ID: i32;
+    // yields after queueing its event, `dispatch` resumes the script with the answer
+    const YIELDS: bool;
+    const ARITY: usize;
+
+    /// The lua function that converts the arguments and queues the event.
+    fn bind<'lua>(
+        lua_ctx: rlua::Context<'lua>,
+        que: EventQueue,
+    ) -> rlua::Result<rlua::Function<'lua>>;
+
+    /// The event of the instruction in a kdef event, `args` are its words like the lua arguments.
+    fn from_kdef(args: &[i16]) -> JyEvent;
+
+    /// Schedules the handler of the event in `script::Plugin`.
+    fn add_handler(set: SystemSet) -> SystemSet;
+}
+
+fn bind<I: Instruct>(lua_ctx: rlua::Context, que: &EventQueue) -> rlua::Result<()> {
+    let func = I::bind(lua_ctx, que.clone())?;
+    if !I::YIELDS {
+        return lua_ctx.globals().set(format!("instruct_{}", I::ID), func);
+    }
+    lua_ctx.globals().set(format!("__instruct_{}", I::ID), func)?;
+    lua_ctx
+        .load(&format!(
+            "function instruct_{0}(...) __instruct_{0}(...) return coroutine.yield() end",
+            I::ID
+        ))
+        .exec()
+}
+
+// choose(title, {options}) returns the picked option, the original builds its menus in lua
+fn bind_choose(lua_ctx: rlua::Context, que: &EventQueue) -> rlua::Result<()> {
+    let que = que.clone();
+    let func = lua_ctx.create_function(move |_, (title, options): (String, Vec<String>)| {
+        que.lock().unwrap().push(JyEvent::Choose(title, options));
+        Ok(())
+    })?;
+    lua_ctx.globals().set("__choose", func)?;
+    lua_ctx
+        .load("function choose(...) __choose(...) return coroutine.yield() end")
+        .exec()
+}
+
+macro_rules! instructs {
+    ($($instruct:ident),*) => {
+        /// Binds every instruction and `choose` into a lua state.
+        pub fn bind_all(lua_ctx: rlua::Context, que: &EventQueue) -> rlua::Result<()> {
+            $(bind::<$instruct>(lua_ctx, que)?;)*
+            bind_choose(lua_ctx, que)
+        }
+
+        /// The event of a kdef instruction and whether it yields, `None` when it is not ported.
+        pub fn kdef_instruct(id: i32, args: &[i16]) -> Option<(JyEvent, bool)> {
+            $(
+                if id == $instruct::ID && args.len() >= $instruct::ARITY {
+                    return Some(($instruct::from_kdef(args), $instruct::YIELDS));
+                }
+            )*
+            None
+        }
+
+        /// Adds the handler of every instruction.
+        pub fn add_handlers(set: SystemSet) -> SystemSet {
+            $(let set = $instruct::add_handler(set);)*
+            set
+        }
+    };
+}
+
+instructs!(
+    Instruct0, Instruct1, Instruct2, Instruct3, Instruct5, Instruct6, Instruct9, Instruct11,
+    Instruct18, Instruct27
+);
 
 #[derive(Debug, Clone)]
 pub enum JyEvent {
     Dialog(String),
+    // talk id, head id, flag, the text is read from talk.grp when it is shown
+    Talk(i32, i32, i32),
     Cls,
-    Sprite,
+    // instruct_27, event id or -1 for the player, the first and the last picture
+    Animate(i16, i16, i16),
     Data(i16, i16, Vec<(usize, i16)>),
     Instruct2(i16, i16),
+    // a yes / no question, the answer resumes the script
+    Ask(String),
+    // a title and its options, the picked one resumes the script
+    Choose(String, Vec<String>),
+    // thing id, resumes the script with whether it is in the backpack
+    HasItem(i16),
+    // war.sta id and whether a lost battle still gives its exp, resumes the script with the victory
+    Battle(i16, i16),
+}
+
+/// What a blocking instruction returns to lua.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Answer {
+    Bool(bool),
+    // the picked option, lua counts them from 1 and gets nil when the choice was cancelled
+    Choice(Option<usize>),
+}
+
+impl Answer {
+    /// The answer as the condition of a kdef jump.
+    pub fn is_yes(&self) -> bool {
+        match self {
+            Answer::Bool(v) => *v,
+            Answer::Choice(v) => v.is_some(),
+        }
+    }
+}
+
+impl<'lua> rlua::ToLua<'lua> for Answer {
+    fn to_lua(self, lua_ctx: rlua::Context<'lua>) -> rlua::Result<rlua::Value<'lua>> {
+        match self {
+            Answer::Bool(v) => rlua::ToLua::to_lua(v, lua_ctx),
+            Answer::Choice(v) => rlua::ToLua::to_lua(v.map(|v| v as i64 + 1), lua_ctx),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct EventScript {
     pub wait_input: bool,
+    // wait for Y / N instead of any key
+    pub wait_answer: bool,
+    // what the blocking instruction returns to lua, resumed by `dispatch`
+    pub answer: Option<Answer>,
+    // an instruct_27 animation is playing
+    pub animating: bool,
     pub events: Vec<JyEvent>,
     pub dispatch: Option<JyEvent>,
 }
@@ -0,0 +1,322 @@
+use bevy::prelude::*;
+
+use crate::game::encoding::TextEncoding;
+use crate::game::structs::{Backpack, Thing};
+use crate::game::util::despawn_screen;
+use crate::game::{GameState, Menu};
+
+pub struct Plugin;
+
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::Inventory).with_system(setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::Inventory)
+                    .with_system(keyboard_inventory.label("input"))
+                    .with_system(show_inventory.after("input")),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Inventory)
+                    .with_system(despawn_screen::<InventoryScreen>),
+            );
+    }
+}
+
+// the tabs of the screen, by Thing::typ
+const KINDS: [(&str, Option<i16>); 6] = [
+    ("全部", None),
+    ("剧情", Some(0)),
+    ("装备", Some(1)),
+    ("秘籍", Some(2)),
+    ("药品", Some(3)),
+    ("暗器", Some(4)),
+];
+
+// the rows shown at once
+const ROWS: usize = 10;
+// the layout of the screen in px, the tip of the picked thing goes next to its row
+const PADDING: f32 = 20.;
+const TAB_HEIGHT: f32 = 42.;
+const ROW_HEIGHT: f32 = 34.;
+const TIP_LEFT: f32 = 360.;
+
+#[derive(Component)]
+pub struct InventoryScreen;
+
+#[derive(Component)]
+enum InventoryText {
+    // the tabs
+    Kind,
+    // a row of the window
+    Row(usize),
+    // the desp() of the picked thing, in the tip
+    Desp,
+}
+
+// the box of the desp() that follows the cursor
+#[derive(Component)]
+struct InventoryTip;
+
+// the tab, the row picked in it and how many rows it has
+#[derive(Clone)]
+struct Inventory {
+    kind: usize,
+    cursor: usize,
+    len: usize,
+}
+
+impl Inventory {
+    fn tab(kind: usize, backpack: &Backpack, things: &[Thing]) -> Self {
+        Inventory {
+            kind,
+            cursor: 0,
+            len: filter_items(backpack, things, KINDS[kind].1).len(),
+        }
+    }
+}
+
+// the rows move like the other menus, the name is the one of the tab
+impl Menu for Inventory {
+    fn up(&self) -> Self {
+        Inventory {
+            cursor: self.cursor.saturating_sub(1),
+            ..self.clone()
+        }
+    }
+
+    fn down(&self) -> Self {
+        Inventory {
+            cursor: (self.cursor + 1).min(self.len.saturating_sub(1)),
+            ..self.clone()
+        }
+    }
+
+    fn to_name(&self) -> String {
+        KINDS[self.kind].0.into()
+    }
+
+    fn to_idx(&self) -> usize {
+        self.cursor
+    }
+
+    fn i18n(&self) -> Option<i32> {
+        None
+    }
+}
+
+/// The (thing, count) of the backpack whose type is `typ`, all of them for None.
+pub fn filter_items(backpack: &Backpack, things: &[Thing], typ: Option<i16>) -> Vec<(usize, i16)> {
+    backpack
+        .items
+        .iter()
+        .filter(|(id, count)| *id >= 0 && *count > 0)
+        .filter_map(|(id, count)| {
+            let thing = things.get(*id as usize)?;
+            if typ.is_none() || typ == Some(thing.typ()) {
+                Some((*id as usize, *count))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    backpack: Res<Backpack>,
+    things: Res<Vec<Thing>>,
+) {
+    commands.insert_resource(Inventory::tab(0, &backpack, &things));
+
+    let font = asset_server.load("fonts/simsun.ttf");
+    let style = |font_size, color| TextStyle {
+        font: font.clone(),
+        font_size,
+        color,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexStart,
+                padding: Rect::all(Val::Px(PADDING)),
+                ..Default::default()
+            },
+            color: Color::rgba(0., 0., 0., 0.8).into(),
+            ..Default::default()
+        })
+        .insert(InventoryScreen)
+        .with_children(|p| {
+            p.spawn_bundle(TextBundle {
+                style: Style {
+                    size: Size::new(Val::Auto, Val::Px(TAB_HEIGHT)),
+                    ..Default::default()
+                },
+                text: Text::with_section("", style(32.0, Color::YELLOW), Default::default()),
+                ..Default::default()
+            })
+            .insert(InventoryText::Kind);
+            for idx in 0..ROWS {
+                p.spawn_bundle(TextBundle {
+                    style: Style {
+                        size: Size::new(Val::Auto, Val::Px(ROW_HEIGHT)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section("", style(28.0, Color::GRAY), Default::default()),
+                    ..Default::default()
+                })
+                .insert(InventoryText::Row(idx));
+            }
+            p.spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: tip_at(0),
+                    max_size: Size::new(Val::Px(400.), Val::Auto),
+                    padding: Rect::all(Val::Px(8.)),
+                    ..Default::default()
+                },
+                color: Color::rgba(0.2, 0.2, 0.3, 0.95).into(),
+                ..Default::default()
+            })
+            .insert(InventoryTip)
+            .with_children(|p| {
+                p.spawn_bundle(TextBundle {
+                    text: Text::with_section("", style(24.0, Color::WHITE), Default::default()),
+                    ..Default::default()
+                })
+                .insert(InventoryText::Desp);
+            });
+        });
+}
+
+// the tip next to the `row` of the window
+fn tip_at(row: usize) -> Rect<Val> {
+    Rect {
+        left: Val::Px(TIP_LEFT),
+        top: Val::Px(PADDING + TAB_HEIGHT + row as f32 * ROW_HEIGHT),
+        ..Default::default()
+    }
+}
+
+// Up/Down pick a thing, Left/Right switch the tab, Esc goes back to the map
+fn keyboard_inventory(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+    inventory: Res<Inventory>,
+    backpack: Res<Backpack>,
+    things: Res<Vec<Thing>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        commands.insert_resource(inventory.up());
+        keyboard_input.clear();
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        commands.insert_resource(inventory.down());
+        keyboard_input.clear();
+    }
+    if keyboard_input.just_pressed(KeyCode::Left) {
+        let kind = (inventory.kind + KINDS.len() - 1) % KINDS.len();
+        commands.insert_resource(Inventory::tab(kind, &backpack, &things));
+        keyboard_input.clear();
+    }
+    if keyboard_input.just_pressed(KeyCode::Right) {
+        let kind = (inventory.kind + 1) % KINDS.len();
+        commands.insert_resource(Inventory::tab(kind, &backpack, &things));
+        keyboard_input.clear();
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.clear();
+        state.pop().unwrap();
+    }
+}
+
+fn show_inventory(
+    inventory: Res<Inventory>,
+    backpack: Res<Backpack>,
+    things: Res<Vec<Thing>>,
+    encoding: Res<TextEncoding>,
+    mut query: Query<(&InventoryText, &mut Text)>,
+    mut tip: Query<&mut Style, With<InventoryTip>>,
+) {
+    if !inventory.is_changed() && !backpack.is_changed() {
+        return;
+    }
+    let items = filter_items(&backpack, &things, KINDS[inventory.kind].1);
+    // the window of rows keeps the cursor in sight
+    let top = (inventory.to_idx() + 1).saturating_sub(ROWS);
+    for mut style in tip.iter_mut() {
+        style.position = tip_at(inventory.to_idx() - top);
+    }
+    for (what, mut text) in query.iter_mut() {
+        let section = &mut text.sections[0];
+        match what {
+            InventoryText::Kind => {
+                section.value = KINDS
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (name, _))| {
+                        if i == inventory.kind {
+                            format!("[{}]", name)
+                        } else {
+                            format!(" {} ", name)
+                        }
+                    })
+                    .collect();
+            }
+            InventoryText::Row(row) => {
+                let idx = top + row;
+                section.value = items
+                    .get(idx)
+                    .map(|(id, count)| format!("{}  x{}", things[*id].name(*encoding), count))
+                    .unwrap_or_default();
+                section.style.color = if idx == inventory.to_idx() {
+                    Color::WHITE
+                } else {
+                    Color::GRAY
+                };
+            }
+            InventoryText::Desp => {
+                section.value = match items.get(inventory.to_idx()) {
+                    Some((id, _)) => things[*id].desp(*encoding),
+                    None => "没有物品".into(),
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::record;
+
+    #[test]
+    fn filter_by_type() {
+        let things = [
+            record::<Thing>(&[("typ", 0, 0)]),
+            record::<Thing>(&[("typ", 0, 1)]),
+            record::<Thing>(&[("typ", 0, 3)]),
+        ];
+        let backpack = Backpack {
+            items: vec![(2, 5), (0, 1), (1, 2), (1, 0), (9, 1)],
+        };
+        assert_eq!(
+            filter_items(&backpack, &things, None),
+            vec![(2, 5), (0, 1), (1, 2)]
+        );
+        assert_eq!(filter_items(&backpack, &things, Some(3)), vec![(2, 5)]);
+        assert_eq!(filter_items(&backpack, &things, Some(4)), vec![]);
+
+        // the rows of a tab
+        let all = Inventory::tab(0, &backpack, &things);
+        assert_eq!(all.up().to_idx(), 0);
+        assert_eq!(all.down().down().down().to_idx(), 2);
+        let medicine = Inventory::tab(4, &backpack, &things);
+        assert_eq!(medicine.to_name(), "药品");
+        assert_eq!(medicine.down().to_idx(), 0);
+        assert_eq!(Inventory::tab(5, &backpack, &things).down().to_idx(), 0);
+    }
+}
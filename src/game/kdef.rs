@@ -0,0 +1,497 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::game::instructs::{kdef_instruct, JyEvent};
+
+// the words after the opcode of instruct_0 .. instruct_67, jumps included
+const KDEF_ARGS: [usize; 68] = [
+    0, 3, 2, 13, 3, 2, 4, 0, 1, 2, 1, 2, 0, 0, 0, 0, 3, 5, 3, 2, 2, 1, 0, 2, 0, 4, 5, 3, 5, 5, 4,
+    3, 2, 3, 2, 4, 3, 1, 4, 1, 1, 3, 2, 3, 6, 2, 2, 2, 2, 2, 7, 0, 0, 0, 0, 4, 1, 0, 0, 0, 5, 2, 6,
+    2, 0, 0, 1, 1,
+];
+
+// the end of an event
+const KDEF_END: i16 = -1;
+// instruct_7, leaves the event
+const KDEF_BREAK: i16 = 7;
+
+// where the two jump words of a conditional instruction start, the words to skip
+// when it is true and when it is false
+fn jumps_at(op: i16) -> Option<usize> {
+    match op {
+        5 | 9 | 11 | 20 | 42 | 61 => Some(0),
+        4 | 6 | 16 | 18 | 31 | 36 | 43 => Some(1),
+        55 => Some(2),
+        28 | 29 | 60 => Some(3),
+        50 => Some(5),
+        _ => None,
+    }
+}
+
+/// A kdef event being run, the native counterpart of an `oldevent_N.lua` coroutine.
+#[derive(Debug, Clone)]
+pub struct KdefEvent {
+    code: Vec<i16>,
+    pc: usize,
+    // the jumps of the conditional instruction waiting for its answer
+    jumps: Option<(i16, i16)>,
+}
+
+impl KdefEvent {
+    pub fn new(code: Vec<i16>) -> Self {
+        KdefEvent {
+            code,
+            pc: 0,
+            jumps: None,
+        }
+    }
+
+    /// Runs until the event ends or a conditional instruction waits for its answer,
+    /// returns the events on the way and whether the event is over.
+    pub fn run(&mut self, answer: Option<bool>, events: &mut Vec<JyEvent>) -> Result<bool, String> {
+        if let Some((yes, no)) = self.jumps.take() {
+            self.jump(if answer.unwrap_or(false) { yes } else { no })?;
+        }
+        loop {
+            let op = match self.code.get(self.pc) {
+                None | Some(&KDEF_END) | Some(&KDEF_BREAK) => return Ok(true),
+                Some(v) => *v,
+            };
+            let len = *KDEF_ARGS
+                .get(op as usize)
+                .ok_or_else(|| format!("unknown instruction {} at word {}", op, self.pc))?;
+            let end = self.pc + 1 + len;
+            if end > self.code.len() {
+                return Err(format!("instruction {} at word {} is cut off", op, self.pc));
+            }
+            // the jumps stay in, as in the calls of the converted scripts
+            let args = self.code[self.pc + 1..end].to_vec();
+            self.pc = end;
+
+            let jumps = jumps_at(op).map(|at| (args[at], args[at + 1]));
+            match (kdef_instruct(op as i32, &args), jumps) {
+                (Some((ev, true)), Some(jumps)) => {
+                    events.push(ev);
+                    self.jumps = Some(jumps);
+                    return Ok(false);
+                }
+                (Some((ev, _)), _) => events.push(ev),
+                // not ported yet, a condition counts as false
+                (None, jumps) => {
+                    warn!("kdef instruction {} {:?} is not supported", op, args);
+                    if let Some((_, no)) = jumps {
+                        self.jump(no)?;
+                    }
+                }
+            }
+        }
+    }
+
+    fn jump(&mut self, words: i16) -> Result<(), String> {
+        let pc = self.pc as i64 + words as i64;
+        if pc < 0 || pc > self.code.len() as i64 {
+            return Err(format!(
+                "jump {} at word {} leaves the event",
+                words, self.pc
+            ));
+        }
+        self.pc = pc as usize;
+        Ok(())
+    }
+}
+
+/// What the comments of a converted script name, read from talk.grp and r0.grp by `kdef2lua`.
+#[derive(Debug, Default)]
+pub struct KdefNames {
+    // the raw talk text, `*` is a line break
+    pub talks: HashMap<i16, String>,
+    // by head portrait
+    pub people: HashMap<i16, String>,
+    pub things: HashMap<i16, String>,
+    pub scenes: HashMap<i16, String>,
+}
+
+impl KdefNames {
+    fn get(names: &HashMap<i16, String>, id: i16) -> String {
+        names.get(&id).cloned().unwrap_or_else(|| id.to_string())
+    }
+
+    // the explanation after an instruction, as the original converter wrote it
+    fn describe(&self, op: i16, args: &[i16]) -> Option<String> {
+        let desc = match (op, args) {
+            (0, _) => ":空语句(清屏)".to_string(),
+            (1, [talk, head, ..]) => format!(
+                "[{}]说: {}",
+                Self::get(&self.people, *head),
+                Self::get(&self.talks, *talk)
+            ),
+            (2, [thing, num, ..]) if *num >= 0 => {
+                format!("得到物品[{}][{}]", Self::get(&self.things, *thing), num)
+            }
+            (2, [thing, num, ..]) => {
+                format!("失去物品[{}][{}]", Self::get(&self.things, *thing), -num)
+            }
+            (3, [s, d, ..]) => {
+                let scene = if *s == -2 {
+                    "当前场景".to_string()
+                } else {
+                    format!("场景[{}]", Self::get(&self.scenes, *s))
+                };
+                let event = if *d == -2 {
+                    "当前场景事件编号".to_string()
+                } else {
+                    format!("场景事件编号 [{}]", d)
+                };
+                format!("修改事件定义:{}:{}", scene, event)
+            }
+            (5, _) => "是否与之过招".to_string(),
+            (7, _) => "结束事件".to_string(),
+            (9, _) => "是否要求加入".to_string(),
+            (11, _) => "是否住宿".to_string(),
+            (18, [thing, ..]) => format!("是否有物品[{}]", Self::get(&self.things, *thing)),
+            _ => return None,
+        };
+        Some(format!("{}({:X}):{}", op, op, desc))
+    }
+}
+
+// one decoded instruction, `args` keep the jumps
+struct Line {
+    at: usize,
+    op: i16,
+    args: Vec<i16>,
+    next: usize,
+}
+
+impl Line {
+    // the words to skip when true and false
+    fn jumps(&self) -> Option<(i16, i16)> {
+        jumps_at(self.op).map(|at| (self.args[at], self.args[at + 1]))
+    }
+
+    fn call(&self) -> String {
+        let args = self.args.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        format!("instruct_{}({})", self.op, args.join(","))
+    }
+
+    fn target(&self, words: i16) -> Option<usize> {
+        let to = self.next as i64 + words as i64;
+        (to >= 0).then_some(to as usize)
+    }
+}
+
+fn decode(code: &[i16]) -> Result<Vec<Line>, String> {
+    let mut lines = vec![];
+    let mut pc = 0;
+    while let Some(&op) = code.get(pc) {
+        if op == KDEF_END {
+            break;
+        }
+        let len = *KDEF_ARGS
+            .get(op as usize)
+            .ok_or_else(|| format!("unknown instruction {} at word {}", op, pc))?;
+        let next = pc + 1 + len;
+        if next > code.len() {
+            return Err(format!("instruction {} at word {} is cut off", op, pc));
+        }
+        lines.push(Line {
+            at: pc,
+            op,
+            args: code[pc + 1..next].to_vec(),
+            next,
+        });
+        pc = next;
+    }
+    Ok(lines)
+}
+
+struct Writer<'a> {
+    names: &'a KdefNames,
+    out: Vec<String>,
+}
+
+impl Writer<'_> {
+    fn push(&mut self, depth: usize, text: String, comment: Option<String>) {
+        let indent = "    ".repeat(depth);
+        self.out.push(match comment {
+            Some(v) => format!("{}{}   --  {}", indent, text, v),
+            None => format!("{}{}", indent, text),
+        });
+    }
+
+    fn statement(&mut self, depth: usize, line: &Line) {
+        let comment = self.names.describe(line.op, &line.args);
+        if line.op == KDEF_BREAK {
+            self.push(depth, "do return; end".into(), comment);
+        } else {
+            self.push(depth, format!("{};", line.call()), comment);
+        }
+    }
+
+    // the lines from word `start` up to word `end` as nested if blocks,
+    // false when a jump does not fit one
+    fn block(&mut self, lines: &[Line], depth: usize, start: usize, end: usize) -> bool {
+        let mut i = match lines.iter().position(|v| v.at >= start) {
+            Some(i) => i,
+            None => return start == end,
+        };
+        while i < lines.len() && lines[i].at < end {
+            let line = &lines[i];
+            let (yes, no) = match line.jumps() {
+                Some(v) => v,
+                None => {
+                    self.statement(depth, line);
+                    i += 1;
+                    continue;
+                }
+            };
+            if yes == 0 && no == 0 {
+                self.statement(depth, line);
+                i += 1;
+                continue;
+            }
+            // the branch that does not jump runs the block up to the jump of the other
+            let (cond, skip) = match (yes, no) {
+                (0, v) if v > 0 => (line.call(), v),
+                (v, 0) if v > 0 => (format!("not {}", line.call()), v),
+                _ => return false,
+            };
+            let block_end = match line.target(skip) {
+                Some(v) if v <= end => v,
+                _ => return false,
+            };
+            let comment = self.names.describe(line.op, &line.args);
+            self.push(depth, format!("if {} then", cond), comment);
+
+            // a last instruction jumping the same either way closes an else
+            let last = lines.iter().rposition(|v| v.at < block_end);
+            let else_end = last
+                .filter(|j| *j > i)
+                .and_then(|j| match lines[j].jumps() {
+                    Some((a, b)) if a == b && a > 0 && lines[j].next == block_end => {
+                        lines[j].target(a).filter(|v| *v <= end).map(|v| (j, v))
+                    }
+                    _ => None,
+                });
+            match else_end {
+                Some((j, else_end)) => {
+                    if !self.block(lines, depth + 1, line.next, lines[j].at) {
+                        return false;
+                    }
+                    self.statement(depth + 1, &lines[j]);
+                    self.push(depth, "else".into(), None);
+                    if !self.block(lines, depth + 1, block_end, else_end) {
+                        return false;
+                    }
+                    self.push(depth, "end".into(), None);
+                    i = lines
+                        .iter()
+                        .position(|v| v.at >= else_end)
+                        .unwrap_or(lines.len());
+                }
+                None => {
+                    if !self.block(lines, depth + 1, line.next, block_end) {
+                        return false;
+                    }
+                    self.push(depth, "end".into(), None);
+                    i = lines
+                        .iter()
+                        .position(|v| v.at >= block_end)
+                        .unwrap_or(lines.len());
+                }
+            }
+        }
+        true
+    }
+
+    // every instruction on its own with gotos, for jumps that are not nested blocks
+    fn flat(&mut self, lines: &[Line]) {
+        let targets = lines
+            .iter()
+            .filter_map(|v| v.jumps().map(|(a, b)| (v, a, b)))
+            .flat_map(|(v, a, b)| {
+                [v.target(a), v.target(b)].map(|to| to.filter(|to| *to != v.next))
+            })
+            .flatten()
+            .collect::<HashSet<_>>();
+        let label = |at: usize| format!("label_{}", at);
+        for line in lines {
+            if targets.contains(&line.at) {
+                self.push(1, format!("::{}::", label(line.at)), None);
+            }
+            match line.jumps() {
+                Some((yes, no)) if (yes, no) != (0, 0) => {
+                    let goto = |words: i16| match line.target(words) {
+                        Some(v) if v != line.next => format!("goto {}", label(v)),
+                        _ => "".into(),
+                    };
+                    let comment = self.names.describe(line.op, &line.args);
+                    let text = match (goto(yes), goto(no)) {
+                        (yes, no) if no.is_empty() => {
+                            format!("if {} then {} end", line.call(), yes)
+                        }
+                        (yes, no) if yes.is_empty() => {
+                            format!("if not {} then {} end", line.call(), no)
+                        }
+                        (yes, no) => format!("if {} then {} else {} end", line.call(), yes, no),
+                    };
+                    self.push(1, text, comment);
+                }
+                _ => self.statement(1, line),
+            }
+        }
+        let end = lines.last().map_or(0, |v| v.next);
+        if targets.iter().any(|v| *v >= end) {
+            self.push(1, format!("::{}::", label(end)), None);
+        }
+    }
+}
+
+/// Decompiles a kdef event into an `oldevent_N.lua` in the style of the converted ones,
+/// nested jumps become if / else blocks and any other jump a goto.
+pub fn to_lua(event_id: usize, code: &[i16], names: &KdefNames) -> Result<String, String> {
+    let lines = decode(code)?;
+    let end = lines.last().map_or(0, |v| v.next);
+    let mut w = Writer { names, out: vec![] };
+    if !w.block(&lines, 1, 0, end) {
+        w.out.clear();
+        w.flat(&lines);
+    }
+
+    let mut out = format!("--function oldevent_{}()\r\n", event_id);
+    for line in w.out {
+        out += &line;
+        out += "\r\n";
+    }
+    out += "--end\r\n\r\n";
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(ev: &mut KdefEvent, answer: Option<bool>) -> (Vec<JyEvent>, bool) {
+        let mut events = vec![];
+        let over = ev.run(answer, &mut events).unwrap();
+        (events, over)
+    }
+
+    #[test]
+    fn conditional_jumps() {
+        // if instruct_18(174) then instruct_2(174, 1) else instruct_0() end, instruct_0()
+        let mut ev = KdefEvent::new(vec![18, 174, 0, 3, 2, 174, 1, 0, 0, -1]);
+        let (events, over) = run(&mut ev, None);
+        assert!(matches!(events.as_slice(), [JyEvent::HasItem(174)]));
+        assert!(!over);
+        let (events, over) = run(&mut ev, Some(true));
+        assert!(matches!(
+            events.as_slice(),
+            [JyEvent::Instruct2(174, 1), JyEvent::Cls, JyEvent::Cls]
+        ));
+        assert!(over);
+
+        let mut ev = KdefEvent::new(vec![18, 174, 0, 3, 2, 174, 1, 0, 0, -1]);
+        run(&mut ev, None);
+        let (events, _) = run(&mut ev, Some(false));
+        assert!(matches!(events.as_slice(), [JyEvent::Cls, JyEvent::Cls]));
+    }
+
+    #[test]
+    fn unsupported_and_broken() {
+        // instruct_4 is not ported, its condition is false, instruct_7 ends the event
+        let mut ev = KdefEvent::new(vec![4, 1, 0, 1, 7, 0, -1]);
+        let (events, over) = run(&mut ev, None);
+        assert!(matches!(events.as_slice(), [JyEvent::Cls]));
+        assert!(over);
+
+        let mut ev = KdefEvent::new(vec![1, 2520, 0]);
+        assert!(ev.run(None, &mut vec![]).is_err());
+        let mut ev = KdefEvent::new(vec![18, 1, 0, -20]);
+        ev.run(None, &mut vec![]).unwrap();
+        assert!(ev.run(Some(false), &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn converted_scripts() {
+        let mut names = KdefNames::default();
+        names.things.insert(174, "银两".into());
+        let code = [
+            3, -2, -2, -2, -2, -1, -1, -1, 3500, 3500, 3500, -2, -2, -2, 0, 2, 174, 100, -1,
+        ];
+        assert_eq!(
+            to_lua(693, &code, &names).unwrap(),
+            include_str!("../../assets/script/oldevent_693.lua")
+        );
+
+        let script = include_str!("../../assets/script/oldevent_691.lua");
+        let talk = script
+            .split("说: ")
+            .nth(1)
+            .unwrap()
+            .split("\r\n")
+            .next()
+            .unwrap();
+        names.talks.insert(2520, talk.into());
+        names.people.insert(0, "WWW".into());
+        let code = [
+            1, 2520, 0, 1, 0, 27, -1, 6890, 6932, 3, -2, 0, 0, 0, -1, -1, -1, -1, -1, -1, -2, -2,
+            -2, 3, -2, 1, -2, -2, 692, -1, -1, -2, -2, -2, -2, -2, -2, -1,
+        ];
+        assert_eq!(to_lua(691, &code, &names).unwrap(), script);
+    }
+
+    #[test]
+    fn jumps_to_lua() {
+        let names = KdefNames::default();
+        let body = |code: &[i16]| {
+            let lua = to_lua(1, code, &names).unwrap();
+            lua.lines()
+                .skip(1)
+                .filter(|v| !v.starts_with("--end") && !v.is_empty())
+                .map(|v| v.split("   --").next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // then and else, the last instruction of then skips the else
+        let code = [18, 174, 0, 4, 0, 5, 1, 1, 0, 0, -1];
+        assert_eq!(
+            body(&code),
+            [
+                "    if instruct_18(174,0,4) then",
+                "        instruct_0();",
+                "        instruct_5(1,1);",
+                "    else",
+                "        instruct_0();",
+                "    end",
+                "    instruct_0();",
+            ]
+        );
+        // nested, negated and a break
+        let code = [9, 6, 0, 0, 4, 0, 0, 1, 7, 0, -1];
+        assert_eq!(
+            body(&code),
+            [
+                "    if not instruct_9(6,0) then",
+                "        instruct_0();",
+                "        if instruct_4(0,0,1) then",
+                "            do return; end",
+                "        end",
+                "    end",
+                "    instruct_0();",
+            ]
+        );
+        // a backward jump is a loop, that takes gotos
+        let code = [0, 5, 0, -4, -1];
+        assert_eq!(
+            body(&code),
+            [
+                "    ::label_0::",
+                "    instruct_0();",
+                "    if not instruct_5(0,-4) then goto label_0 end",
+            ]
+        );
+        assert!(to_lua(1, &[1, 2520], &names).is_err());
+    }
+}
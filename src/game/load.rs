@@ -1,14 +1,15 @@
-use std::mem::size_of;
-
 use bevy::asset::LoadState;
 use bevy::prelude::*;
 
 use crate::game::assets::*;
+use crate::game::encoding::TextEncoding;
 use crate::game::mmap::MMapStatus;
+use crate::game::save::{slot_files, PlayTime, SlotMeta, SAVE_DIR};
 use crate::game::script::JyEvent;
 use crate::game::structs::*;
 use crate::game::util::{ImageCache, PosXY, RenderHelper};
-use crate::game::{structs, GameState};
+use crate::game::error::DataErrors;
+use crate::game::{records, structs, GameState, JyDataError, Record};
 
 pub struct Plugin;
 
@@ -24,6 +25,23 @@ impl bevy::prelude::Plugin for Plugin {
     }
 }
 
+/// Which data set `loading` reads, inserted by the splash menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameSlot {
+    NewGame,
+    // R1 - R3, slot 0 is the data of a new game
+    Saved(usize),
+}
+
+impl GameSlot {
+    pub fn slot(&self) -> usize {
+        match self {
+            GameSlot::NewGame => 0,
+            GameSlot::Saved(n) => *n,
+        }
+    }
+}
+
 pub struct GameLoad {
     pub grp_handles: Vec<(GrpDataType, Handle<GrpAsset>)>,
     pub data_handles: Vec<Handle<DataAsset>>,
@@ -34,7 +52,9 @@ pub fn load(
     mut grp_assets: ResMut<Assets<GrpAsset>>,
     mut ds_assets: ResMut<Assets<DataAsset>>,
     game_load: Res<GameLoad>,
+    game_slot: Res<GameSlot>,
     server: Res<AssetServer>,
+    encoding: Res<TextEncoding>,
     mut state: ResMut<State<GameState>>,
 ) {
     let iter = game_load
@@ -43,13 +63,38 @@ pub fn load(
         .map(|v| v.1.id)
         .chain(game_load.data_handles.iter().map(|v| v.id));
 
-    if server.get_group_load_state(iter) != LoadState::Loaded {
-        return;
+    match server.get_group_load_state(iter) {
+        LoadState::Loaded => {}
+        LoadState::Failed => {
+            let errors = game_load
+                .grp_handles
+                .iter()
+                .map(|v| v.1.id)
+                .chain(game_load.data_handles.iter().map(|v| v.id))
+                .filter(|v| server.get_load_state(*v) == LoadState::Failed)
+                .map(|v| {
+                    JyDataError::Missing(
+                        server
+                            .get_handle_path(v)
+                            .map(|p| p.path().display().to_string())
+                            .unwrap_or_default(),
+                    )
+                })
+                .collect();
+            commands.insert_resource(DataErrors(errors));
+            commands.remove_resource::<GameLoad>();
+            state.set(GameState::DataError).unwrap();
+            return;
+        }
+        _ => return,
     }
     println!("load {}", game_load.grp_handles.len());
 
     let mut scene_num = 0;
-    let mut mmap_pos = PosXY::new(0, 0);
+    let mut sta = SceneStatus::default();
+    let mut m_sta = MMapStatus::default();
+    let mut next_state = GameState::Smap;
+    let mut errors = vec![];
     game_load
         .grp_handles
         .clone()
@@ -59,17 +104,24 @@ pub fn load(
             let gs = grp_assets.remove(handle.1.clone()).unwrap();
             match game_load.grp_handles[idx].0 {
                 GrpDataType::Ranger => {
-                    let gd = GameData::new(gs);
+                    let gd = match GameData::new(gs) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            errors.push(e);
+                            return;
+                        }
+                    };
+                    gd.log(*encoding);
 
-                    mmap_pos.update(gd.base.person_x as usize, gd.base.person_y as usize);
+                    next_state = restore_status(&gd.base, *game_slot, &mut sta, &mut m_sta);
                     scene_num = gd.scenes.len();
+                    commands.insert_resource(Backpack::new(&gd.base));
                     commands.insert_resource(gd.base);
                     commands.insert_resource(gd.scenes);
                     commands.insert_resource(gd.people);
                     commands.insert_resource(gd.shops);
                     commands.insert_resource(gd.things);
                     commands.insert_resource(gd.wukongs);
-                    commands.insert_resource(Backpack::default())
                 },
                 GrpDataType::AllSin => {
                     debug!(
@@ -78,7 +130,10 @@ pub fn load(
                         gs.data.len(),
                         scene_num * structs::SCENE_WIDTH * structs::SCENE_HEIGHT * 12
                     );
-                    commands.insert_resource(SData::new(gs));
+                    match SData::new(gs, scene_num) {
+                        Ok(v) => commands.insert_resource(v),
+                        Err(e) => errors.push(e),
+                    }
                 }
                 GrpDataType::AllDef => {
                     debug!(
@@ -86,15 +141,22 @@ pub fn load(
                         gs.data.len(),
                         scene_num * structs::DNUM * 11 * 2
                     );
-                    commands.insert_resource(DData::new(gs));
-
-
+                    match DData::new(gs, scene_num) {
+                        Ok(v) => commands.insert_resource(v),
+                        Err(e) => errors.push(e),
+                    }
                 }
                 GrpDataType::Smap => {
                     commands.insert_resource(SMapTexture(TextureMap::new(gs)));
                 }
                 GrpDataType::Hdgrp => {
-
+                    commands.insert_resource(HeadTexture(TextureMap::new(gs)));
+                }
+                GrpDataType::Talk => {
+                    commands.insert_resource(Talks(gs));
+                }
+                GrpDataType::Kdef => {
+                    commands.insert_resource(Kdef(gs));
                 }
                 GrpDataType::Thing => {
 
@@ -102,6 +164,12 @@ pub fn load(
                 GrpDataType::Mmap => {
                     commands.insert_resource(MMapTexture(TextureMap::new(gs)));
                 }
+                GrpDataType::WarFld => {
+                    commands.insert_resource(WarFld(gs));
+                }
+                GrpDataType::Wmap => {
+                    commands.insert_resource(WMapTexture(TextureMap::new(gs)));
+                }
             }
         });
 
@@ -138,24 +206,34 @@ pub fn load(
                     // buildy
                     commands.insert_resource(MmapBuildY(structs::load_i16(&ds.data)));
                 }
+                6 => {
+                    // war.sta
+                    match records::<WarSta>(&ds.data) {
+                        Ok(v) => commands.insert_resource(v),
+                        Err(e) => errors.push(e),
+                    }
+                }
                 _ => {}
             }
         });
+    if !errors.is_empty() {
+        commands.insert_resource(DataErrors(errors));
+        commands.remove_resource::<GameLoad>();
+        state.set(GameState::DataError).unwrap();
+        return;
+    }
     commands.init_resource::<ImageCache>();
 
-    let mut sta = SceneStatus::default();
-    sta.cur_s = structs::ENTRY_SCENE;
-    sta.pos = PosXY::new(structs::ENTRY_X, structs::ENTRY_Y);
-    sta.cur_pic = NEW_PERSON;
-    sta.is_new_game = true;
-    if sta.is_new_game {
-        state.set(GameState::Smap).unwrap();
-        // state.set(GameState::Mmap).unwrap();
-    }
-    commands.insert_resource(sta);
+    let play_secs = match *game_slot {
+        GameSlot::NewGame => 0,
+        GameSlot::Saved(n) => SlotMeta::read(std::path::Path::new(SAVE_DIR), n)
+            .map(|v| v.play_secs)
+            .unwrap_or(0),
+    };
+    commands.insert_resource(PlayTime(play_secs as f64));
 
-    let mut m_sta = MMapStatus::default();
-    m_sta.pos = mmap_pos;
+    state.set(next_state).unwrap();
+    commands.insert_resource(sta);
     commands.insert_resource(m_sta);
     commands.init_resource::<RenderHelper>();
     commands.remove_resource::<GameLoad>();
@@ -168,8 +246,12 @@ pub enum GrpDataType {
     AllDef,
     Smap,
     Hdgrp,
+    Talk,
+    Kdef,
     Thing,
     Mmap,
+    WarFld,
+    Wmap,
 }
 
 #[derive(Copy, Clone)]
@@ -182,18 +264,57 @@ pub enum RawData {
     BuildY
 }
 
-pub fn loading(mut commands: Commands, res: Res<AssetServer>) {
-    debug!("start to load data");
+/// Restores where the player was from `Base` and returns the map to enter,
+/// a new game always starts at `ENTRY_SCENE`.
+fn restore_status(
+    base: &Base,
+    slot: GameSlot,
+    sta: &mut SceneStatus,
+    m_sta: &mut MMapStatus,
+) -> GameState {
+    m_sta.pos = PosXY::new(base.person_x as usize, base.person_y as usize);
+    m_sta.pos.facing = Some(MoveDir::from_dir(base.person_dir));
+    m_sta.on_boat = base.boat != 0;
+    m_sta.boat_pos = PosXY::new(base.boat_x as usize, base.boat_y as usize);
+    m_sta.boat_pos.facing = Some(MoveDir::from_dir(base.boat_dir));
+
+    if slot == GameSlot::NewGame {
+        sta.cur_s = structs::ENTRY_SCENE;
+        sta.pos = PosXY::new(structs::ENTRY_X, structs::ENTRY_Y);
+        sta.cur_pic = NEW_PERSON;
+        sta.is_new_game = true;
+        return GameState::Smap;
+    }
+
+    // SaveRecord stores the sub scene in Base["无用"], -1 when saved on the main map
+    if base.useless < 0 {
+        return GameState::Mmap;
+    }
+    sta.cur_s = base.useless as usize;
+    sta.pos = PosXY::new(base.person_x_scene as usize, base.person_y_scene as usize);
+    sta.pos.facing = Some(MoveDir::from_dir(base.person_dir));
+    GameState::Smap
+}
+
+pub fn loading(mut commands: Commands, res: Res<AssetServer>, game_slot: Option<Res<GameSlot>>) {
+    let slot = game_slot.map(|v| *v).unwrap_or(GameSlot::NewGame);
+    debug!("start to load data of {:?}", slot);
+    let (r, s, d) = slot_files(slot.slot());
     let handles = vec![
-        (GrpDataType::Ranger, res.load("org/data/ranger.grp")),
-        (GrpDataType::AllSin, res.load("org/data/allsin.grp")),
-        (GrpDataType::AllDef, res.load("org/data/alldef.grp")),
+        (GrpDataType::Ranger, res.load(format!("org/data/{}.grp", r).as_str())),
+        (GrpDataType::AllSin, res.load(format!("org/data/{}.grp", s).as_str())),
+        (GrpDataType::AllDef, res.load(format!("org/data/{}.grp", d).as_str())),
         // smap
         (GrpDataType::Smap, res.load("org/data/smap.grp")),
         (GrpDataType::Hdgrp,res.load("org/data/hdgrp.grp")),
+        (GrpDataType::Talk, res.load("org/data/talk.grp")),
+        (GrpDataType::Kdef, res.load("org/data/kdef.grp")),
         (GrpDataType::Thing,res.load("org/data/thing.grp")),
         // mmap
         (GrpDataType::Mmap, res.load("org/data/mmap.grp")),
+        // battle
+        (GrpDataType::WarFld, res.load("org/data/warfld.grp")),
+        (GrpDataType::Wmap, res.load("org/data/wmap.grp")),
     ];
 
     let data_h = vec![
@@ -204,15 +325,18 @@ pub fn loading(mut commands: Commands, res: Res<AssetServer>) {
         res.load("org/data/building.002"),
         res.load("org/data/buildx.002"),
         res.load("org/data/buildy.002"),
+        // battle
+        res.load("org/data/war.sta"),
     ];
 
+    commands.insert_resource(slot);
     commands.insert_resource(GameLoad {
         grp_handles: handles,
         data_handles: data_h,
     });
 }
 
-struct GameData {
+pub(crate) struct GameData {
     pub base: Base,
     pub people: Vec<Person>,
     pub things: Vec<Thing>,
@@ -222,43 +346,31 @@ struct GameData {
 }
 
 impl GameData {
-    pub fn new(asset: GrpAsset) -> Self {
+    pub fn new(asset: GrpAsset) -> Result<Self, JyDataError> {
         let gd = GameData {
-            base: Base::new(asset.idx(0).unwrap()),
-            people: asset
-                .idx(1)
-                .unwrap()
-                .chunks(size_of::<Person>())
-                .map(|v| Person::new(v))
-                .collect(),
-            things: asset
-                .idx(2)
-                .unwrap()
-                .chunks(size_of::<Thing>())
-                .map(|v| Thing::new(v))
-                .collect(),
-            scenes: asset
-                .idx(3)
-                .unwrap()
-                .chunks(size_of::<structs::Scene>())
-                .map(|v| structs::Scene::new(v))
-                .collect(),
-            wukongs: vec![],
-            shops: vec![],
+            base: Base::from_bytes(asset.idx(0)?)?,
+            people: asset.records(1)?,
+            things: asset.records(2)?,
+            scenes: asset.records(3)?,
+            wukongs: asset.records(4)?,
+            shops: asset.records(5)?,
         };
         debug!("base: {:?}", gd.base);
+        Ok(gd)
+    }
+
+    fn log(&self, encoding: TextEncoding) {
         debug!(
             "talent ones {:?}",
-            gd.people
+            self.people
                 .iter()
                 .filter(|v| { v.talent > 85 })
-                .map(|v| { (v.name(), v.alias(), v) })
+                .map(|v| { (v.name(encoding), v.alias(encoding), v) })
                 .collect::<Vec<_>>()
         );
         debug!(
             "start scene {:?}",
-            gd.scenes.get(70).map(|v| { (v.name(), v) })
+            self.scenes.get(70).map(|v| { (v.name(encoding), v) })
         );
-        gd
     }
 }
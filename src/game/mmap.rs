@@ -43,6 +43,8 @@ pub struct MMapScreen;
 pub struct MMapStatus {
     pub cur_pic: usize,
     pub pos: PosXY,
+    pub on_boat: bool,
+    pub boat_pos: PosXY,
 }
 
 struct MMapCanvasWriter<'a, 'w, 's> {
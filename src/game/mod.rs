@@ -1,19 +1,31 @@
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::io::Cursor;
 
 pub use assets::*;
+pub use error::JyDataError;
 use bevy::ecs::schedule::ShouldRun;
 use bevy::prelude::*;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 mod assets;
+pub(crate) mod battle;
+pub mod combat;
+pub mod encoding;
+pub mod error;
 mod instructs;
+mod inventory;
+pub mod kdef;
 mod hint;
 mod load;
 mod mmap;
+pub mod save;
+mod save_menu;
 pub mod script;
 mod smap;
 mod sound;
 mod splash;
+mod system_menu;
 pub mod structs;
 pub(crate) mod util;
 
@@ -33,6 +45,16 @@ pub enum GameState {
     Mmap,
     // this should always be pushed with other states
     Interaction,
+    // pushed on top of Smap or Mmap by Esc, its menu opens Inventory or Save in its place
+    System,
+    // the things of the backpack
+    Inventory,
+    // in place of System
+    Save,
+    // pushed on top of Interaction by instruct_6
+    Battle,
+    // the data files could not be loaded
+    DataError,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
@@ -45,12 +67,18 @@ pub struct Plugin;
 impl bevy::prelude::Plugin for Plugin {
     fn build(&self, app: &mut App) {
         app.add_state(GameState::Splash)
+            .init_resource::<encoding::TextEncoding>()
             .add_plugin(splash::Plugin)
             .add_plugin(load::Plugin)
             .add_plugin(script::Plugin)
             .add_plugin(hint::Plugin)
             .add_plugin(mmap::Plugin)
-            .add_plugin(smap::Plugin);
+            .add_plugin(smap::Plugin)
+            .add_plugin(system_menu::Plugin)
+            .add_plugin(inventory::Plugin)
+            .add_plugin(save_menu::Plugin)
+            .add_plugin(battle::Plugin)
+            .add_plugin(error::Plugin);
 
         #[cfg(not(target_arch = "wasm32"))]
         app.add_plugin(sound::Plugin);
@@ -85,3 +113,121 @@ macro_rules! read {
         $ident.read($vall);
     };
 }
+
+#[macro_export]
+macro_rules! write_to {
+    ($ident : ident, u8, $val : expr) => {
+        $ident.write_u8($val).unwrap()
+    };
+    ($ident : ident, i16, $val : expr) => {
+        $ident.write_i16::<LittleEndian>($val).unwrap()
+    };
+    ($ident : ident, u16, $val : expr) => {
+        $ident.write_u16::<LittleEndian>($val).unwrap()
+    };
+}
+
+/// A fixed-layout record of the original data files, implemented by `#[derive(JyRecord)]`.
+pub trait Record: Sized {
+    const SIZE: usize;
+    // (field, byte offset) in declaration order
+    const OFFSETS: &'static [(&'static str, usize)];
+
+    fn from_bytes(data: &[u8]) -> Result<Self, JyDataError>;
+    fn to_bytes(&self) -> Vec<u8>;
+
+    fn offset_of(field: &str) -> Option<usize> {
+        Self::OFFSETS
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, offset)| *offset)
+    }
+}
+
+/// Reads a plain array of records, as a section of ranger.grp or war.sta is.
+pub fn records<T: Record>(data: &[u8]) -> Result<Vec<T>, JyDataError> {
+    data.chunks(T::SIZE).map(T::from_bytes).collect()
+}
+
+/// A zeroed record with the i16 fields set, (field, index in it, value), for the tests.
+#[cfg(test)]
+pub fn record<T: Record>(fields: &[(&str, usize, i16)]) -> T {
+    let mut data = vec![0; T::SIZE];
+    for (field, i, v) in fields {
+        let at = T::offset_of(field).unwrap() + i * 2;
+        data[at..at + 2].copy_from_slice(&v.to_le_bytes());
+    }
+    T::from_bytes(&data).unwrap()
+}
+
+/// The field types a `Record` can be made of, all little endian.
+pub trait RecordField: Sized {
+    const SIZE: usize;
+
+    fn read(c: &mut Cursor<&[u8]>) -> Self;
+    fn write(&self, out: &mut Vec<u8>);
+}
+
+impl RecordField for u8 {
+    const SIZE: usize = 1;
+
+    fn read(c: &mut Cursor<&[u8]>) -> Self {
+        read!(c, u8)
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_to!(out, u8, *self)
+    }
+}
+
+impl RecordField for i16 {
+    const SIZE: usize = 2;
+
+    fn read(c: &mut Cursor<&[u8]>) -> Self {
+        read!(c, i16)
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_to!(out, i16, *self)
+    }
+}
+
+impl RecordField for u16 {
+    const SIZE: usize = 2;
+
+    fn read(c: &mut Cursor<&[u8]>) -> Self {
+        read!(c, u16)
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_to!(out, u16, *self)
+    }
+}
+
+// fixed-length strings are plain [u8; N]
+impl<T: RecordField, const N: usize> RecordField for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+
+    fn read(c: &mut Cursor<&[u8]>) -> Self {
+        [(); N].map(|_| T::read(c))
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        self.iter().for_each(|v| v.write(out));
+    }
+}
+
+// interleaved pairs, like the (item, count) list of the base record
+impl<A: RecordField, B: RecordField> RecordField for (A, B) {
+    const SIZE: usize = A::SIZE + B::SIZE;
+
+    fn read(c: &mut Cursor<&[u8]>) -> Self {
+        let a = A::read(c);
+        (a, B::read(c))
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        self.0.write(out);
+        self.1.write(out);
+    }
+}
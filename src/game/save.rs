@@ -0,0 +1,193 @@
+use std::fs;
+use std::io::{Cursor, Error, ErrorKind, Result};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::game::structs::*;
+use crate::game::{GrpAsset, Record};
+
+// the original keeps the saves next to the data files
+// CC.R_GRPFilename={[0]="data/ranger.grp","data/r1.grp","data/r2.grp","data/r3.grp"}
+// CC.S_Filename={[0]="data/allsin.grp","data/s1.grp","data/s2.grp","data/s3.grp"}
+// CC.D_Filename={[0]="data/alldef.grp","data/d1.grp","data/d2.grp","data/d3.grp"}
+pub const SAVE_DIR: &str = "assets/org/data";
+
+/// The (r, s, d) file stems of a slot, slot 0 is the data of a new game.
+pub fn slot_files(slot: usize) -> (String, String, String) {
+    match slot {
+        0 => ("ranger".into(), "allsin".into(), "alldef".into()),
+        n => (format!("r{}", n), format!("s{}", n), format!("d{}", n)),
+    }
+}
+
+pub fn slot_exists(slot: usize) -> bool {
+    // there is no file system to look at, let the asset server try it
+    #[cfg(target_arch = "wasm32")]
+    return true;
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let (r, _, _) = slot_files(slot);
+        Path::new(SAVE_DIR).join(format!("{}.grp", r)).exists()
+    }
+}
+
+/// Reads r*.grp and r*.idx of a slot the same way the grp loader does.
+pub fn read_ranger(dir: &Path, slot: usize) -> Result<GrpAsset> {
+    let (r, _, _) = slot_files(slot);
+    read_grp(dir, &r)
+}
+
+/// Reads a `name.grp` with its `name.idx`.
+pub fn read_grp(dir: &Path, name: &str) -> Result<GrpAsset> {
+    let data = fs::read(dir.join(format!("{}.grp", name)))?;
+    let idx_data = fs::read(dir.join(format!("{}.idx", name)))?;
+    let mut idx = vec![0];
+    let mut cursor = Cursor::new(idx_data);
+    while let Ok(ret) = cursor.read_u32::<LittleEndian>() {
+        idx.push(ret as usize);
+    }
+    Ok(GrpAsset { idx, data })
+}
+
+pub const THUMB_SIZE: usize = 64;
+
+/// Total seconds played, restored from the slot meta when a save is loaded.
+#[derive(Default)]
+pub struct PlayTime(pub f64);
+
+/// What the original format has no room for, kept in r*.meta next to the save:
+/// the play time in seconds (u32) followed by a THUMB_SIZE x THUMB_SIZE rgba thumbnail.
+pub struct SlotMeta {
+    pub play_secs: u32,
+    pub thumb: Vec<u8>,
+}
+
+impl SlotMeta {
+    pub fn read(dir: &Path, slot: usize) -> Result<Self> {
+        let (r, _, _) = slot_files(slot);
+        let mut c = Cursor::new(fs::read(dir.join(format!("{}.meta", r)))?);
+        let play_secs = c.read_u32::<LittleEndian>()?;
+        let thumb = c.into_inner().split_off(4);
+        if thumb.len() != THUMB_SIZE * THUMB_SIZE * 4 {
+            return Err(Error::new(ErrorKind::InvalidData, "bad thumbnail size"));
+        }
+        Ok(SlotMeta { play_secs, thumb })
+    }
+
+    pub fn write(&self, dir: &Path, slot: usize) -> Result<()> {
+        let (r, _, _) = slot_files(slot);
+        let mut data = vec![];
+        data.write_u32::<LittleEndian>(self.play_secs)?;
+        data.extend_from_slice(&self.thumb);
+        fs::write(dir.join(format!("{}.meta", r)), data)
+    }
+}
+
+/// Everything that goes into a save slot.
+pub struct SaveData<'a> {
+    pub base: &'a Base,
+    pub people: &'a [Person],
+    pub things: &'a [Thing],
+    pub scenes: &'a [Scene],
+    pub wugongs: &'a [Wugong],
+    pub shops: &'a [Shop],
+    pub s_data: &'a SData,
+    pub d_data: &'a DData,
+}
+
+impl<'a> SaveData<'a> {
+    /// Encodes the r*.grp data and its r*.idx, the idx holds the end offset of every section.
+    pub fn encode_ranger(&self) -> (Vec<u8>, Vec<u8>) {
+        let sections: Vec<Vec<u8>> = vec![
+            self.base.to_bytes(),
+            self.people.iter().flat_map(|v| v.to_bytes()).collect(),
+            self.things.iter().flat_map(|v| v.to_bytes()).collect(),
+            self.scenes.iter().flat_map(|v| v.to_bytes()).collect(),
+            self.wugongs.iter().flat_map(|v| v.to_bytes()).collect(),
+            self.shops.iter().flat_map(|v| v.to_bytes()).collect(),
+        ];
+
+        let mut grp = vec![];
+        let mut idx = vec![];
+        for section in sections {
+            grp.extend(section);
+            idx.write_u32::<LittleEndian>(grp.len() as u32).unwrap();
+        }
+        (grp, idx)
+    }
+
+    pub fn write(&self, dir: &Path, slot: usize) -> Result<()> {
+        let (r, s, d) = slot_files(slot);
+        let (grp, idx) = self.encode_ranger();
+        fs::write(dir.join(format!("{}.grp", r)), grp)?;
+        fs::write(dir.join(format!("{}.idx", r)), idx)?;
+        fs::write(dir.join(format!("{}.grp", s)), self.s_data.as_bytes())?;
+        fs::write(dir.join(format!("{}.grp", d)), self.d_data.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+    use std::io::Read;
+
+    use super::*;
+    use crate::game::load::GameData;
+    use crate::game::test::load_gs;
+
+    fn read_all(file: &str) -> Vec<u8> {
+        let mut data = vec![];
+        File::open(file).unwrap().read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn ranger_round_trip() {
+        let gs = load_gs(
+            "./assets/org/data/ranger.grp".into(),
+            "./assets/org/data/ranger.idx".into(),
+        )
+        .unwrap();
+        let gd = GameData::new(gs).unwrap();
+        let s_data = SData::new(
+            GrpAsset {
+                idx: vec![0],
+                data: read_all("./assets/org/data/allsin.grp"),
+            },
+            gd.scenes.len(),
+        )
+        .unwrap();
+        let d_data = DData::new(
+            GrpAsset {
+                idx: vec![0],
+                data: read_all("./assets/org/data/alldef.grp"),
+            },
+            gd.scenes.len(),
+        )
+        .unwrap();
+        let save = SaveData {
+            base: &gd.base,
+            people: &gd.people,
+            things: &gd.things,
+            scenes: &gd.scenes,
+            wugongs: &gd.wukongs,
+            shops: &gd.shops,
+            s_data: &s_data,
+            d_data: &d_data,
+        };
+
+        let (grp, idx) = save.encode_ranger();
+        assert_eq!(grp, read_all("./assets/org/data/ranger.grp"));
+        assert_eq!(idx, read_all("./assets/org/data/ranger.idx"));
+        assert_eq!(
+            save.s_data.as_bytes(),
+            read_all("./assets/org/data/allsin.grp").as_slice()
+        );
+        assert_eq!(
+            save.d_data.as_bytes(),
+            read_all("./assets/org/data/alldef.grp").as_slice()
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use jy_derive::JyMenu;
+
+use crate::game::encoding::TextEncoding;
+use crate::game::load::GameData;
+use crate::game::mmap::MMapStatus;
+use crate::game::save::*;
+use crate::game::structs;
+use crate::game::structs::*;
+use crate::game::util::{despawn_screen, ImageCache};
+use crate::game::{GameState, Menu};
+
+pub struct Plugin;
+
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::Save).with_system(setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::Save)
+                    .with_system(keyboard_save_menu)
+                    .with_system(write_save.exclusive_system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Save).with_system(despawn_screen::<SaveScreen>),
+            )
+            .add_system(tick_play_time);
+    }
+}
+
+// R1 - R3, slot 0 is the data of a new game and never written
+#[derive(Clone, JyMenu)]
+enum SaveOption {
+    Slot1,
+    Slot2,
+    Slot3,
+}
+
+const SLOT_NAMES: [&str; 3] = ["进度一", "进度二", "进度三"];
+
+#[derive(Component)]
+pub struct SaveScreen;
+
+#[derive(Component)]
+pub struct SaveSlotRow(usize);
+
+// the slot chosen in the menu, written by `write_save`
+struct SaveRequest(usize);
+
+// the thumbnail of where we are, taken when the menu opens
+struct SaveThumb(Vec<u8>);
+
+struct SlotInfo {
+    scene: String,
+    leader: String,
+    level: i16,
+    meta: Option<SlotMeta>,
+}
+
+fn slot_info(slot: usize, encoding: TextEncoding) -> Option<SlotInfo> {
+    let dir = Path::new(SAVE_DIR);
+    let gd = GameData::new(read_ranger(dir, slot).ok()?).ok()?;
+    let scene = if gd.base.useless >= 0 {
+        gd.scenes
+            .get(gd.base.useless as usize)
+            .map(|v| v.name(encoding))
+            .unwrap_or_default()
+    } else {
+        "主地图".into()
+    };
+    let leader = gd.people.get(gd.base.teams[0].max(0) as usize);
+    Some(SlotInfo {
+        scene,
+        leader: leader.map(|v| v.name(encoding)).unwrap_or_default(),
+        level: leader.map(|v| v.level()).unwrap_or_default(),
+        meta: SlotMeta::read(dir, slot).ok(),
+    })
+}
+
+fn format_time(secs: u32) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn tick_play_time(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    mb_play_time: Option<ResMut<PlayTime>>,
+) {
+    if let Some(mut play_time) = mb_play_time {
+        match state.current() {
+            GameState::Smap | GameState::Mmap | GameState::Interaction => {
+                play_time.0 += time.delta_seconds_f64();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Averages the solid pixels of every tile picture into one thumbnail pixel.
+fn thumbnail<F>(image_cache: &mut ImageCache, tile: F) -> Vec<u8>
+where
+    F: Fn(usize, usize) -> Option<(MapType, usize)>,
+{
+    let mut colors: HashMap<(MapType, usize), [u8; 4]> = HashMap::new();
+    let mut out = vec![0; THUMB_SIZE * THUMB_SIZE * 4];
+    for y in 0..THUMB_SIZE {
+        for x in 0..THUMB_SIZE {
+            let key = match tile(x, y) {
+                Some(v) => v,
+                None => continue,
+            };
+            let color = *colors.entry(key).or_insert_with(|| {
+                let mut sum = [0u32; 3];
+                let mut n = 0;
+                if let Some((_, _, Some(image))) = image_cache.get_image(key.0, key.1) {
+                    image.data.chunks(4).filter(|v| v[3] > 0).for_each(|v| {
+                        sum[0] += v[0] as u32;
+                        sum[1] += v[1] as u32;
+                        sum[2] += v[2] as u32;
+                        n += 1;
+                    });
+                }
+                if n == 0 {
+                    [0, 0, 0, 0xFF]
+                } else {
+                    [
+                        (sum[0] / n) as u8,
+                        (sum[1] / n) as u8,
+                        (sum[2] / n) as u8,
+                        0xFF,
+                    ]
+                }
+            });
+            let i = (y * THUMB_SIZE + x) * 4;
+            out[i..i + 4].copy_from_slice(&color);
+        }
+    }
+    out
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    state: Res<State<GameState>>,
+    mut images: ResMut<Assets<Image>>,
+    mut image_cache: ResMut<ImageCache>,
+    sta: Res<SceneStatus>,
+    m_sta: Res<MMapStatus>,
+    s_data: Res<SData>,
+    mmap_earth: Res<MmapEarth>,
+    mmap_surface: Res<MmapSurface>,
+    mmap_building: Res<MmapBuilding>,
+    encoding: Res<TextEncoding>,
+) {
+    let thumb = if state.inactives().last() == Some(&GameState::Smap) {
+        thumbnail(&mut image_cache, |x, y| {
+            let building = s_data.get_texture(sta.cur_s, x, y, 1).unwrap_or(0);
+            let id = if building > 0 {
+                building
+            } else {
+                s_data.get_texture(sta.cur_s, x, y, 0).unwrap_or(0)
+            };
+            (id > 0).then(|| (MapType::Smap, id as usize / 2))
+        })
+    } else {
+        // the part of the main map around us
+        let x0 = (m_sta.pos.x as usize).saturating_sub(THUMB_SIZE / 2);
+        let y0 = (m_sta.pos.y as usize).saturating_sub(THUMB_SIZE / 2);
+        thumbnail(&mut image_cache, |x, y| {
+            let offset = (y0 + y) * MMAP_WIDTH + x0 + x;
+            [&mmap_building.0, &mmap_surface.0, &mmap_earth.0]
+                .iter()
+                .filter_map(|layer| layer.get(offset))
+                .find(|v| **v > 0)
+                .map(|v| (MapType::Mmap, *v as usize / 2))
+        })
+    };
+    commands.insert_resource(SaveThumb(thumb));
+    commands.insert_resource(SaveOption::Slot1);
+
+    let font = asset_server.load("fonts/simsun.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0., 0., 0., 0.8).into(),
+            ..Default::default()
+        })
+        .insert(SaveScreen)
+        .with_children(|p| {
+            for idx in 0..SaveOption::count() {
+                let info = slot_info(idx + 1, *encoding);
+                let label = match &info {
+                    Some(v) => format!(
+                        "{}  {}  {} Lv{}  {}",
+                        SLOT_NAMES[idx],
+                        v.scene,
+                        v.leader,
+                        v.level,
+                        format_time(v.meta.as_ref().map(|m| m.play_secs).unwrap_or(0)),
+                    ),
+                    None => format!("{}  --", SLOT_NAMES[idx]),
+                };
+                let thumb = info.and_then(|v| v.meta).map(|meta| {
+                    images.add(Image::new(
+                        Extent3d {
+                            width: THUMB_SIZE as u32,
+                            height: THUMB_SIZE as u32,
+                            depth_or_array_layers: 1,
+                        },
+                        TextureDimension::D2,
+                        meta.thumb,
+                        TextureFormat::Rgba8UnormSrgb,
+                    ))
+                });
+
+                p.spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        margin: Rect::all(Val::Px(10.)),
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|row| {
+                    let size = Size::new(
+                        Val::Px(THUMB_SIZE as f32 * 2.),
+                        Val::Px(THUMB_SIZE as f32 * 2.),
+                    );
+                    match thumb {
+                        Some(image) => {
+                            row.spawn_bundle(ImageBundle {
+                                style: Style {
+                                    size,
+                                    ..Default::default()
+                                },
+                                image: image.into(),
+                                ..Default::default()
+                            });
+                        }
+                        None => {
+                            row.spawn_bundle(NodeBundle {
+                                style: Style {
+                                    size,
+                                    ..Default::default()
+                                },
+                                color: Color::DARK_GRAY.into(),
+                                ..Default::default()
+                            });
+                        }
+                    }
+                    row.spawn_bundle(TextBundle {
+                        style: Style {
+                            margin: Rect::all(Val::Px(20.)),
+                            ..Default::default()
+                        },
+                        text: Text::with_section(
+                            label,
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 40.0,
+                                color: if idx == 0 { Color::WHITE } else { Color::GRAY },
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(SaveSlotRow(idx));
+                });
+            }
+        });
+}
+
+fn keyboard_save_menu(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+    options: Res<SaveOption>,
+    mut query: Query<(&SaveSlotRow, &mut Text)>,
+) {
+    for (row, mut text) in query.iter_mut() {
+        text.sections[0].style.color = if row.0 == options.to_idx() {
+            Color::WHITE
+        } else {
+            Color::GRAY
+        };
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        commands.insert_resource(options.up());
+        keyboard_input.clear();
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        commands.insert_resource(options.down());
+        keyboard_input.clear();
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        keyboard_input.clear();
+        commands.insert_resource(SaveRequest(options.to_idx() + 1));
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.clear();
+        state.pop().unwrap();
+    }
+}
+
+/// Puts where we are and what we carry back into `Base`, the way SaveRecord does.
+fn sync_base(
+    base: &mut Base,
+    in_scene: bool,
+    sta: &SceneStatus,
+    m_sta: &MMapStatus,
+    backpack: &Backpack,
+) {
+    base.person_x = m_sta.pos.x as i16;
+    base.person_y = m_sta.pos.y as i16;
+    base.boat = m_sta.on_boat as i16;
+    base.boat_x = m_sta.boat_pos.x as i16;
+    base.boat_y = m_sta.boat_pos.y as i16;
+    if let Some(dir) = &m_sta.boat_pos.facing {
+        base.boat_dir = dir.to_dir();
+    }
+    if in_scene {
+        base.useless = sta.cur_s as i16;
+        base.person_x_scene = sta.pos.x as i16;
+        base.person_y_scene = sta.pos.y as i16;
+        base.person_dir = sta.pos.facing().to_dir();
+    } else {
+        base.useless = -1;
+        base.person_dir = m_sta.pos.facing().to_dir();
+    }
+    for (i, item) in base.items.iter_mut().enumerate() {
+        *item = backpack.items.get(i).copied().unwrap_or((-1, 0));
+    }
+}
+
+fn write_save(world: &mut World) {
+    let slot = match world.remove_resource::<SaveRequest>() {
+        Some(v) => v.0,
+        None => return,
+    };
+    let in_scene = world
+        .get_resource::<State<GameState>>()
+        .unwrap()
+        .inactives()
+        .last()
+        == Some(&GameState::Smap);
+
+    world.resource_scope(|world, mut base: Mut<Base>| {
+        sync_base(
+            &mut base,
+            in_scene,
+            world.get_resource::<SceneStatus>().unwrap(),
+            world.get_resource::<MMapStatus>().unwrap(),
+            world.get_resource::<Backpack>().unwrap(),
+        );
+    });
+
+    let save = SaveData {
+        base: world.get_resource::<Base>().unwrap(),
+        people: world.get_resource::<Vec<Person>>().unwrap(),
+        things: world.get_resource::<Vec<Thing>>().unwrap(),
+        scenes: world.get_resource::<Vec<structs::Scene>>().unwrap(),
+        wugongs: world.get_resource::<Vec<Wugong>>().unwrap(),
+        shops: world.get_resource::<Vec<Shop>>().unwrap(),
+        s_data: world.get_resource::<SData>().unwrap(),
+        d_data: world.get_resource::<DData>().unwrap(),
+    };
+    let meta = SlotMeta {
+        play_secs: world.get_resource::<PlayTime>().unwrap().0 as u32,
+        thumb: world.get_resource::<SaveThumb>().unwrap().0.clone(),
+    };
+    let dir = Path::new(SAVE_DIR);
+    match save.write(dir, slot).and_then(|_| meta.write(dir, slot)) {
+        Ok(_) => info!("saved to slot {}", slot),
+        Err(e) => error!("failed to save slot {}: {:?}", slot, e),
+    }
+
+    world
+        .get_resource_mut::<State<GameState>>()
+        .unwrap()
+        .pop()
+        .unwrap();
+}
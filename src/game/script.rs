@@ -2,18 +2,22 @@
 
 use bevy::app::Events;
 use bevy::prelude::*;
-use lazy_static::lazy_static;
 
+use crate::game::assets::{LuaScript, LuaScriptLoader};
+pub use crate::game::instructs::*;
+use crate::game::kdef::KdefEvent;
 use crate::game::structs::*;
 use crate::game::GameState;
-pub use crate::game::instructs::*;
-use rlua::Lua;
-use std::fs::File;
-use std::io::*;
+use crate::settings::Settings;
+use bevy::asset::LoadState;
+use rlua::{Lua, RegistryKey, Thread, ThreadStatus};
+use std::fmt;
+use std::str::FromStr;
 use std::sync::Mutex;
 
 #[cfg(test)]
 pub mod test {
+    use super::*;
     use rlua::Lua;
 
     #[test]
@@ -50,6 +54,134 @@ pub mod test {
             );
         });
     }
+
+    #[test]
+    fn runtime_per_world() {
+        let mut a = ScriptRuntime::default();
+        let mut b = ScriptRuntime::default();
+
+        let evs = a
+            .start(
+                "a.lua",
+                b"instruct_0() if instruct_18(1) then instruct_0() end instruct_0()",
+            )
+            .unwrap();
+        assert!(matches!(
+            evs.as_slice(),
+            [JyEvent::Cls, JyEvent::HasItem(1)]
+        ));
+        assert!(b.resume(Some(Answer::Bool(true))).unwrap().is_empty());
+
+        assert!(matches!(
+            a.resume(Some(Answer::Bool(false))).unwrap().as_slice(),
+            [JyEvent::Cls]
+        ));
+        assert!(a.resume(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn current_thing() {
+        // an item event that only opens to the key
+        let source = b"if JY.CurrentThing == 5 then instruct_0() end";
+        let mut runtime = ScriptRuntime {
+            current_thing: Some(5),
+            ..Default::default()
+        };
+        let evs = runtime.start("item.lua", source).unwrap();
+        assert!(matches!(evs.as_slice(), [JyEvent::Cls]));
+        runtime.current_thing = Some(6);
+        assert!(runtime.start("item.lua", source).unwrap().is_empty());
+        runtime.current_thing = None;
+        assert!(runtime.start("item.lua", source).unwrap().is_empty());
+    }
+
+    #[test]
+    fn replace_unfinished() {
+        let mut runtime = ScriptRuntime::default();
+        // if instruct_18(174) then instruct_2(174, 1) else instruct_0() end, waits on the answer
+        let evs = runtime
+            .start_kdef(1, vec![18, 174, 0, 3, 2, 174, 1, 0, 0, -1])
+            .unwrap();
+        assert!(matches!(evs.as_slice(), [JyEvent::HasItem(174)]));
+        let evs = runtime
+            .start("a.lua", b"if instruct_5() then instruct_0() end")
+            .unwrap();
+        assert!(matches!(evs.as_slice(), [JyEvent::Ask(_)]));
+        // the answer goes to the lua event, the kdef one is gone
+        assert!(matches!(
+            runtime.resume(Some(Answer::Bool(true))).unwrap().as_slice(),
+            [JyEvent::Cls]
+        ));
+        assert!(runtime.resume(None).unwrap().is_empty());
+
+        // and so is an unfinished coroutine
+        runtime.start("a.lua", b"instruct_5()").unwrap();
+        runtime.start("b.lua", b"instruct_0()").unwrap();
+        assert!(runtime.resume(Some(Answer::Bool(true))).unwrap().is_empty());
+    }
+
+    #[test]
+    fn script_errors() {
+        let mut runtime = ScriptRuntime::default();
+        let e = runtime.start("bad.lua", b"instruct_0(\n").unwrap_err();
+        assert_eq!(e.script, "bad.lua");
+        assert!(e.message.contains("bad.lua:2:"), "{}", e.message);
+
+        let e = runtime
+            .start("bad.lua", b"instruct_0()\ninstruct_2(1)")
+            .unwrap_err();
+        assert!(
+            e.message.contains("instruct_2 takes 2 arguments"),
+            "{}",
+            e.message
+        );
+        assert!(e.message.contains("bad.lua:2:"), "{}", e.message);
+        // the failed coroutine is gone
+        assert!(runtime.resume(Some(Answer::Bool(true))).unwrap().is_empty());
+    }
+
+    #[test]
+    fn choose_options() {
+        let mut runtime = ScriptRuntime::default();
+        let evs = runtime
+            .start(
+                "a.lua",
+                b"local i = choose('t', {'a', 'b'}) if i == 2 then instruct_0() end \
+                  if choose('t', {'a'}) == nil then instruct_0() end",
+            )
+            .unwrap();
+        assert!(matches!(
+            evs.as_slice(),
+            [JyEvent::Choose(title, options)] if title == "t" && options == &["a", "b"]
+        ));
+        assert!(matches!(
+            runtime.resume(Some(Answer::Choice(Some(1)))).unwrap().as_slice(),
+            [JyEvent::Cls, JyEvent::Choose(..)]
+        ));
+        assert!(matches!(
+            runtime.resume(Some(Answer::Choice(None))).unwrap().as_slice(),
+            [JyEvent::Cls]
+        ));
+    }
+
+    #[test]
+    fn instruct_arguments() {
+        let que = EventQueue::default();
+        let lua = init_lua(&que);
+        lua.context(|lua_ctx| {
+            let err = lua_ctx.load("instruct_2(1)").exec().unwrap_err();
+            // the cause of a callback error is only in its debug form
+            assert!(format!("{:?}", err).contains("instruct_2 takes 2 arguments, got 1"));
+            let err = lua_ctx.load("instruct_2(1, {})").exec().unwrap_err();
+            assert!(format!("{:?}", err).contains("instruct_2 argument 2 `num`"));
+
+            lua_ctx.load("instruct_2(1, 2, 0, 0)").exec().unwrap();
+        });
+        assert!(matches!(
+            que.lock().unwrap().as_slice(),
+            [JyEvent::Instruct2(1, 2)]
+        ));
+    }
 }
 
 #[derive(Component)]
@@ -59,16 +191,30 @@ pub struct Plugin;
 
 impl bevy::prelude::Plugin for Plugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_update(GameState::Interaction)
-                .with_system(collect.label("collect"))
-                .with_system(dispatch.label("dispatch").after("collect"))
-                .with_system(handle_instruct_0.after("dispatch").label("execute"))
-                .with_system(handle_instruct_1.after("dispatch").label("execute"))
-                .with_system(handle_instruct_2.after("dispatch").label("execute"))
-                .with_system(handle_instruct_3.after("dispatch").label("execute"))
-                .with_system(handle_instruct_27.after("dispatch").label("execute"))
-                .with_system(check_input.after("execute")),
+        let source = app
+            .world
+            .get_resource::<Settings>()
+            .map_or(ScriptSource::Lua, |v| v.script_source());
+        app.insert_resource(ScriptRuntime {
+            source,
+            ..Default::default()
+        })
+        .add_event::<ScriptError>()
+        .add_system(report_errors)
+        .add_system(hide_error_panel)
+        .add_asset::<LuaScript>()
+        .add_asset_loader(LuaScriptLoader)
+        .add_system_set(
+            add_handlers(
+                SystemSet::on_update(GameState::Interaction)
+                    .with_system(start_pending.before("collect"))
+                    .with_system(collect.label("collect"))
+                    .with_system(dispatch.label("dispatch").after("collect")),
+            )
+            // the questions of instruct_5, 9 and 11 and the option lists share one box
+            .with_system(handle_choice.after("dispatch").label("execute"))
+            .with_system(check_input.after("execute"))
+            .with_system(choice_menu.after("execute")),
         );
     }
 }
@@ -76,14 +222,17 @@ impl bevy::prelude::Plugin for Plugin {
 fn dispatch(
     mut commands: Commands,
     mut mb_ev_script: Option<ResMut<EventScript>>,
+    mut runtime: ResMut<ScriptRuntime>,
     mut state: ResMut<State<GameState>>,
+    mut errors: EventWriter<ScriptError>,
+    query: Query<Entity, With<DialogBox>>,
 ) {
     if mb_ev_script.is_none() {
         return;
     }
 
     let ev_script = mb_ev_script.as_mut().unwrap();
-    if ev_script.wait_input {
+    if ev_script.wait_input || ev_script.animating {
         return;
     }
 
@@ -91,6 +240,22 @@ fn dispatch(
         panic!("event should be handled");
     }
 
+    if let Some(answer) = ev_script.answer.take() {
+        match runtime.resume(Some(answer)) {
+            Ok(events) => ev_script.events.extend(events),
+            Err(e) => {
+                // the rest of the event is dropped, the game goes on
+                errors.send(e);
+                for entity in query.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                commands.remove_resource::<EventScript>();
+                state.pop().unwrap();
+                return;
+            }
+        }
+    }
+
     if ev_script.events.len() == 0 {
         commands.remove_resource::<EventScript>();
         state.pop().unwrap();
@@ -113,6 +278,9 @@ fn collect(
     }
     let mut ev_script = EventScript {
         wait_input: false,
+        wait_answer: false,
+        answer: None,
+        animating: false,
         events: vec![],
         dispatch: None,
     };
@@ -124,134 +292,355 @@ fn collect(
 }
 
 fn check_input(keycode: ResMut<Input<KeyCode>>, mut mb_ev_script: Option<ResMut<EventScript>>) {
-    if mb_ev_script.is_some() && mb_ev_script.as_ref().unwrap().wait_input {
-        if keycode.just_pressed(KeyCode::Return) || keycode.just_pressed(KeyCode::Space) {
-            mb_ev_script.as_mut().unwrap().wait_input = false;
+    if mb_ev_script.is_none() || !mb_ev_script.as_ref().unwrap().wait_input {
+        return;
+    }
+    let ev_script = mb_ev_script.as_mut().unwrap();
+    // `choice_menu` takes the answers
+    if ev_script.wait_answer {
+        return;
+    }
+    if keycode.just_pressed(KeyCode::Return) || keycode.just_pressed(KeyCode::Space) {
+        ev_script.wait_input = false;
+    }
+}
+
+/// A script that failed to load or run, the interaction running it is aborted.
+#[derive(Debug, Clone)]
+pub struct ScriptError {
+    pub script: String,
+    // the lua message with the line, and the traceback of a failed instruction
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "event script {} failed: {}", self.script, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+// the display of a callback error leaves out what the callback returned
+fn lua_message(e: &rlua::Error) -> String {
+    match e {
+        rlua::Error::CallbackError { traceback, cause } => {
+            format!("{}\n{}", lua_message(cause), traceback)
         }
+        rlua::Error::RuntimeError(v) => v.clone(),
+        rlua::Error::SyntaxError { message, .. } => message.clone(),
+        v => v.to_string(),
     }
 }
 
-lazy_static! {
-    pub static ref S_LUA: Mutex<Lua> = Mutex::new(init_lua());
-    pub static ref S_EVENT_QUE: Mutex<Vec<JyEvent>> = Mutex::new(vec![]);
+/// Where the events are read from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScriptSource {
+    // the converted `oldevent_N.lua`, kdef.grp for the events without one
+    Lua,
+    // always the original kdef.grp
+    Kdef,
+}
+
+impl FromStr for ScriptSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lua" => Ok(ScriptSource::Lua),
+            "kdef" => Ok(ScriptSource::Kdef),
+            _ => Err(format!("unknown script source {}", s)),
+        }
+    }
+}
+
+// what `execute_n` asked for, started by `start_pending`
+enum Pending {
+    // waits for the asset server
+    Lua(i16, String, Handle<LuaScript>),
+    Kdef(i16),
+}
+
+/// The lua state of a world, the instructions queue their events into it and
+/// the running event script is kept as a coroutine between frames.
+pub struct ScriptRuntime {
+    pub source: ScriptSource,
+    // the thing used on the event about to start, JY.CurrentThing of the lua scripts
+    pub current_thing: Option<i16>,
+    lua: Mutex<Lua>,
+    que: EventQueue,
+    // the running event, a lua coroutine or a kdef event
+    script: String,
+    thread: Option<RegistryKey>,
+    kdef: Option<KdefEvent>,
+    pending: Option<Pending>,
+}
+
+impl Default for ScriptRuntime {
+    fn default() -> Self {
+        let que = EventQueue::default();
+        ScriptRuntime {
+            source: ScriptSource::Lua,
+            current_thing: None,
+            lua: Mutex::new(init_lua(&que)),
+            que,
+            script: String::new(),
+            thread: None,
+            kdef: None,
+            pending: None,
+        }
+    }
+}
+
+impl ScriptRuntime {
+    /// Starts a script as the running event, returns the events it queued before it ended or yielded.
+    /// `name` is the path the lua errors point to.
+    pub fn start(&mut self, name: &str, source: &[u8]) -> Result<Vec<JyEvent>, ScriptError> {
+        self.stop();
+        self.script = name.into();
+        let current_thing = self.current_thing;
+        let thread = self.lua.get_mut().unwrap().context(|lua_ctx| {
+            let jy: rlua::Table = lua_ctx.globals().get("JY")?;
+            jy.set("CurrentThing", current_thing)?;
+            // `@` makes lua report `name:line` instead of the source
+            let func = lua_ctx
+                .load(source)
+                .set_name(&format!("@{}", name))?
+                .into_function()?;
+            let thread = lua_ctx.create_thread(func)?;
+            lua_ctx.create_registry_value(thread)
+        });
+        match thread {
+            Ok(key) => self.thread = Some(key),
+            Err(e) => return Err(self.error(&e)),
+        }
+        self.resume(None)
+    }
+
+    /// Starts the instruction words of a kdef.grp event as the running event.
+    pub fn start_kdef(
+        &mut self,
+        event_id: i16,
+        code: Vec<i16>,
+    ) -> Result<Vec<JyEvent>, ScriptError> {
+        self.stop();
+        self.script = format!("kdef event {}", event_id);
+        self.kdef = Some(KdefEvent::new(code));
+        self.resume(None)
+    }
+
+    // drops the event left unfinished, a new one takes its place
+    fn stop(&mut self) {
+        self.que.lock().unwrap().clear();
+        self.kdef = None;
+        if let Some(key) = self.thread.take() {
+            self.lua
+                .get_mut()
+                .unwrap()
+                .context(|lua_ctx| lua_ctx.remove_registry_value(key))
+                .unwrap();
+        }
+    }
+
+    /// Runs the event coroutine until it ends or yields on a blocking instruction,
+    /// `answer` is what that instruction returns. Returns the events queued on the way.
+    pub fn resume(&mut self, answer: Option<Answer>) -> Result<Vec<JyEvent>, ScriptError> {
+        if let Some(mut kdef) = self.kdef.take() {
+            let mut events = vec![];
+            return match kdef.run(answer.map(|v| v.is_yes()), &mut events) {
+                Ok(over) => {
+                    if !over {
+                        self.kdef = Some(kdef);
+                    }
+                    Ok(events)
+                }
+                Err(message) => Err(ScriptError {
+                    script: self.script.clone(),
+                    message,
+                }),
+            };
+        }
+
+        let mut result = Ok(());
+        if let Some(key) = self.thread.take() {
+            let thread_key = &mut self.thread;
+            self.lua.get_mut().unwrap().context(|lua_ctx| {
+                let thread: Thread = lua_ctx.registry_value(&key).unwrap();
+                result = thread.resume::<_, ()>(answer);
+                if result.is_ok() && thread.status() == ThreadStatus::Resumable {
+                    *thread_key = Some(key);
+                } else {
+                    lua_ctx.remove_registry_value(key).unwrap();
+                }
+            });
+        }
+        let events = self.que.lock().unwrap().drain(..).collect();
+        match result {
+            Ok(()) => Ok(events),
+            Err(e) => Err(self.error(&e)),
+        }
+    }
+
+    fn error(&self, e: &rlua::Error) -> ScriptError {
+        ScriptError {
+            script: self.script.clone(),
+            message: lua_message(e),
+        }
+    }
 }
 
 pub fn execute_n(
-    commands: &mut Commands,
-    state: &mut ResMut<State<GameState>>,
-    events: &mut ResMut<Events<JyEvent>>,
+    state: &mut State<GameState>,
+    runtime: &mut ScriptRuntime,
+    asset_server: &AssetServer,
     event_id: i16,
 ) {
-    let mut ev_guard = S_EVENT_QUE.lock().unwrap();
-    ev_guard.clear();
-    drop(ev_guard);
     println!("exec event {}", event_id);
-    S_LUA.lock().unwrap().context(|lua_ctx| {
-        let mut data = vec![];
-        File::open(format!("assets/script/oldevent_{}.lua", event_id))
-            .unwrap()
-            .read_to_end(&mut data)
-            .unwrap();
-        lua_ctx.load(data.as_slice()).exec().unwrap();
-        let mut ev_guard = S_EVENT_QUE.lock().unwrap();
-        events.extend(ev_guard.clone().into_iter());
-        ev_guard.clear();
+    runtime.pending = Some(match runtime.source {
+        ScriptSource::Lua => {
+            let path = format!("script/oldevent_{}.lua", event_id);
+            let handle = asset_server.load(path.as_str());
+            Pending::Lua(event_id, path, handle)
+        }
+        ScriptSource::Kdef => Pending::Kdef(event_id),
     });
-
     state.push(GameState::Interaction).unwrap();
 }
 
-fn init_lua() -> Lua {
-    let lua = Lua::new();
+// runs the script of `execute_n` once it is loaded
+fn start_pending(
+    mut runtime: ResMut<ScriptRuntime>,
+    asset_server: Res<AssetServer>,
+    scripts: Res<Assets<LuaScript>>,
+    kdef: Option<Res<Kdef>>,
+    mut state: ResMut<State<GameState>>,
+    mut events: ResMut<Events<JyEvent>>,
+    mut errors: EventWriter<ScriptError>,
+) {
+    let start_kdef = |runtime: &mut ScriptRuntime, event_id: i16| match kdef.as_ref() {
+        Some(kdef) => match kdef.get(event_id as usize) {
+            Ok(code) => runtime.start_kdef(event_id, code),
+            Err(e) => Err(ScriptError {
+                script: format!("kdef event {}", event_id),
+                message: e.to_string(),
+            }),
+        },
+        None => Err(ScriptError {
+            script: format!("kdef event {}", event_id),
+            message: "kdef.grp is not loaded".into(),
+        }),
+    };
+    let result = match runtime.pending.as_ref() {
+        None => return,
+        Some(Pending::Kdef(event_id)) => {
+            let event_id = *event_id;
+            start_kdef(&mut runtime, event_id)
+        }
+        Some(Pending::Lua(event_id, path, handle)) => {
+            let (event_id, path) = (*event_id, path.clone());
+            match scripts.get(handle) {
+                Some(script) => runtime.start(&path, &script.source),
+                None if asset_server.get_load_state(handle) != LoadState::Failed => return,
+                // most events were never converted
+                None if kdef.is_some() => {
+                    debug!("no {}, run kdef event {}", path, event_id);
+                    start_kdef(&mut runtime, event_id)
+                }
+                None => Err(ScriptError {
+                    script: path,
+                    message: "failed to load".into(),
+                }),
+            }
+        }
+    };
+    runtime.pending = None;
+    match result {
+        Ok(evs) if !evs.is_empty() => events.extend(evs),
+        Ok(_) => {
+            state.pop().unwrap();
+        }
+        Err(e) => {
+            errors.send(e);
+            state.pop().unwrap();
+        }
+    }
+}
 
+fn init_lua(events: &EventQueue) -> Lua {
+    let lua = Lua::new();
     lua.context(|lua_ctx| {
-        let globals = lua_ctx.globals();
-        globals
-            .set(
-                "instruct_0",
-                lua_ctx
-                    .create_function_mut(|_, ()| {
-                        let ev = instruct_0();
-                        let mut ev_guard = S_EVENT_QUE.lock().unwrap();
-                        ev_guard.push(ev);
-                        Ok(true)
-                    })
-                    .unwrap(),
-            )
-            .unwrap();
+        bind_all(lua_ctx, events)?;
+        lua_ctx.globals().set("JY", lua_ctx.create_table()?)
+    })
+    .unwrap();
+    lua
+}
 
-        globals
-            .set(
-                "instruct_1",
-                lua_ctx
-                    .create_function_mut(|_, (talk_id, head_id, flag): (i32, i32, i32)| {
-                        let ev = instruct_1(talk_id, head_id, flag);
-                        let mut ev_guard = S_EVENT_QUE.lock().unwrap();
-                        ev_guard.push(ev);
-                        Ok(true)
-                    })
-                    .unwrap(),
-            )
-            .unwrap();
+#[derive(Component)]
+struct ScriptErrorPanel(Timer);
 
-        globals
-            .set(
-                "instruct_2",
-                lua_ctx
-                    .create_function_mut(|_, (thing_id, num): (i16, i16)| {
-                        let ev = instruct_2(thing_id, num);
-                        let mut ev_guard = S_EVENT_QUE.lock().unwrap();
-                        ev_guard.push(ev);
-                        Ok(true)
-                    })
-                    .unwrap(),
-            )
-            .unwrap();
+// logs the script errors, in dev mode the last one is also shown on screen for a while
+fn report_errors(
+    mut commands: Commands,
+    mut errors: EventReader<ScriptError>,
+    settings: Option<Res<Settings>>,
+    asset_server: Res<AssetServer>,
+    query: Query<Entity, With<ScriptErrorPanel>>,
+) {
+    let mut last = None;
+    for e in errors.iter() {
+        error!("{}", e);
+        last = Some(e);
+    }
+    let e = match last {
+        Some(e) if settings.map(|v| v.dev()) == Some(true) => e,
+        _ => return,
+    };
 
-        globals
-            .set(
-                "instruct_3",
-                lua_ctx
-                    .create_function_mut(
-                        |_,
-                         (s, d, v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10): (
-                            i16,
-                            i16,
-                            i16,
-                            i16,
-                            i16,
-                            i16,
-                            i16,
-                            i16,
-                            i16,
-                            i16,
-                            i16,
-                            i16,
-                            i16,
-                        )| {
-                            let ev = instruct_3(s, d, v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10);
-                            let mut ev_guard = S_EVENT_QUE.lock().unwrap();
-                            ev_guard.push(ev);
-                            Ok(true)
-                        },
-                    )
-                    .unwrap(),
-            )
-            .unwrap();
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                max_size: Size::new(Val::Percent(80.), Val::Undefined),
+                padding: Rect::all(Val::Px(10.)),
+                ..Default::default()
+            },
+            color: Color::rgba(0.3, 0., 0., 0.85).into(),
+            ..Default::default()
+        })
+        .insert(ScriptErrorPanel(Timer::from_seconds(10., false)))
+        .with_children(|p| {
+            p.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    e.to_string(),
+                    TextStyle {
+                        font: asset_server.load("fonts/simsun.ttf"),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
 
-        globals
-            .set(
-                "instruct_27",
-                lua_ctx
-                    .create_function_mut(|_, (id, start_pic, end_pic): (i16, i16, i16)| {
-                        let ev = instruct_27(id, start_pic, end_pic);
-                        let mut ev_guard = S_EVENT_QUE.lock().unwrap();
-                        ev_guard.push(ev);
-                        Ok(true)
-                    })
-                    .unwrap(),
-            )
-            .unwrap();
-    });
-    lua
+fn hide_error_panel(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut ScriptErrorPanel)>,
+) {
+    for (entity, mut panel) in query.iter_mut() {
+        if panel.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
 }
@@ -7,7 +7,10 @@ use std::ops::ControlFlow;
 use bevy::app::Events;
 use bevy::prelude::*;
 
-use crate::game::script::{JyEvent, SpriteMeta};
+use crate::game::encoding::TextEncoding;
+use crate::game::script::{
+    spawn_choice, Choice, ChoiceRow, DialogBox, ScriptRuntime, SpriteMeta,
+};
 use crate::game::structs::*;
 pub use crate::game::util::ImageCache;
 use crate::game::util::{despawn_screen, Canvas, RenderHelper};
@@ -21,8 +24,11 @@ impl bevy::prelude::Plugin for Plugin {
             .add_system_set(
                 SystemSet::on_update(GameState::Smap)
                     .with_system(movement.label("move"))
-                    .with_system(on_event.after("move")),
+                    .with_system(on_event.after("move"))
+                    .with_system(open_item_menu)
+                    .with_system(use_item_menu),
             )
+            .add_event::<Stepped>()
             .add_system_set(
                 SystemSet::on_exit(GameState::Smap).with_system(despawn_screen::<SMapScreen>),
             );
@@ -74,7 +80,8 @@ impl<'a, 'w, 's> SMapCanvasWriter<'a, 'w, 's> {
         if level == 3 {
             self.location_set.insert(loc);
         }
-        let id = self.s_data.get_texture(cur_s, w, h, level);
+        // the tiles out of the scene are empty
+        let id = self.s_data.get_texture(cur_s, w, h, level).unwrap_or(0);
         if id <= 0 {
             return;
         }
@@ -88,20 +95,20 @@ impl<'a, 'w, 's> SMapCanvasWriter<'a, 'w, 's> {
             1 => {
                 // building
                 // add building offset
-                transform.translation.y += self.s_data.get_texture(cur_s, w, h, 4) as f32;
+                transform.translation.y += self.s_data.get_texture(cur_s, w, h, 4).unwrap_or(0) as f32;
                 pic_id = id / 2;
             }
             2 => {
                 // air
                 // add air offset
-                transform.translation.y += self.s_data.get_texture(cur_s, w, h, 5) as f32;
+                transform.translation.y += self.s_data.get_texture(cur_s, w, h, 5).unwrap_or(0) as f32;
                 pic_id = id / 2;
             }
             3 => {
                 // event
                 // add event offset
-                transform.translation.y += self.s_data.get_texture(cur_s, w, h, 4) as f32;
-                pic_id = self.d_data.get_d(cur_s as usize, id as usize, 7) / 2;
+                transform.translation.y += self.s_data.get_texture(cur_s, w, h, 4).unwrap_or(0) as f32;
+                pic_id = self.d_data.get_d(cur_s as usize, id as usize, 7).unwrap_or(0) / 2;
             }
             _ => {
                 todo!()
@@ -159,31 +166,8 @@ pub fn setup(
     debug!("start smap rending");
 
     // draw sprite
-    let x = sta.pos.x;
-    let y = sta.pos.y;
-
     if sta.is_new_game {
-        if let Some((image_h, meta, _)) = image_cache.get_image(MapType::Smap, sta.cur_pic) {
-            let mut transform = Transform::from_xyz(0., 0., 3.0);
-            debug!(
-                "sprite init pos {},{}",
-                transform.translation.x, transform.translation.y
-            );
-
-            let height = s_data.get_texture(sta.cur_s, x as usize, y as usize, 4);
-            println!("height is {}", height);
-
-            transform.translation.x -= meta.2 - meta.0 as f32 / 2.;
-            transform.translation.y += meta.3 - meta.1 as f32 / 2. + height as f32;
-            commands
-                .spawn_bundle(SpriteBundle {
-                    transform,
-                    texture: image_h,
-                    ..Default::default()
-                })
-                .insert(SMapScreen)
-                .insert(Me);
-        }
+        spawn_me_pic(&mut commands, &mut image_cache, &s_data, &sta, sta.cur_pic);
     } else {
         let entity = render_helper.render_sprite(&mut commands, MapType::Smap,&mut images);
         commands.entity(entity).insert(Me).insert(SMapScreen);
@@ -193,6 +177,60 @@ pub fn setup(
 
 }
 
+/// Shows the player as the still picture `pic`, as the new game and `instruct_27` do.
+pub fn spawn_me_pic(
+    commands: &mut Commands,
+    image_cache: &mut ImageCache,
+    s_data: &SData,
+    sta: &SceneStatus,
+    pic: usize,
+) {
+    if let Some((image_h, meta, _)) = image_cache.get_image(MapType::Smap, pic) {
+        let mut transform = Transform::from_xyz(0., 0., 3.0);
+        debug!(
+            "sprite init pos {},{}",
+            transform.translation.x, transform.translation.y
+        );
+
+        let height = s_data
+            .get_texture(sta.cur_s, sta.pos.x as usize, sta.pos.y as usize, 4)
+            .unwrap_or(0);
+        transform.translation.x -= meta.2 - meta.0 as f32 / 2.;
+        transform.translation.y += meta.3 - meta.1 as f32 / 2. + height as f32;
+        commands
+            .spawn_bundle(SpriteBundle {
+                transform,
+                texture: image_h,
+                ..Default::default()
+            })
+            .insert(SMapScreen)
+            .insert(Me);
+    }
+}
+
+/// Draws the event at (x, y) again with the picture `pic`, as `draw_at` does for level 3.
+pub fn redraw_event(
+    commands: &mut Commands,
+    render_helper: &mut RenderHelper,
+    s_data: &SData,
+    sta: &SceneStatus,
+    boxes: &Query<&JyBox>,
+    (x, y): (usize, usize),
+    pic: usize,
+) {
+    for bx in boxes.iter().filter(|bx| (bx.1, bx.2) == (x, y)) {
+        commands.entity(bx.0).despawn_recursive();
+    }
+    if pic == 0 {
+        return;
+    }
+    let mut transform = Transform::from_translation(sta.pos.to_real(x as f32, y as f32, 3.));
+    transform.translation.y += s_data.get_texture(sta.cur_s, x, y, 4).unwrap_or(0) as f32;
+    if let Some(v) = render_helper.render(commands, MapType::Smap, pic, transform) {
+        commands.entity(v).insert(SMapScreen).insert(JyBox(v, x, y));
+    }
+}
+
 #[derive(Component)]
 pub struct Me;
 
@@ -202,33 +240,142 @@ pub struct NetCell;
 #[derive(Component)]
 pub struct JyBox(pub Entity, pub usize, pub usize);
 
+/// A step of the player onto (x, y), for the step events.
+pub struct Stepped(pub usize, pub usize);
+
+// the things of the open item list, in the order of its rows
+pub struct ItemMenu(Vec<i16>);
+
+// the record at (x, y) as `SceneStatus::cur_d` and the event `trigger` starts on it
+fn event_at(
+    d_data: &DData,
+    s_data: &SData,
+    cur_s: usize,
+    (x, y): (i32, i32),
+    trigger: Trigger,
+) -> Option<((usize, usize, usize), i16)> {
+    if x < 0 || y < 0 {
+        return None;
+    }
+    let (x, y) = (x as usize, y as usize);
+    let d = s_data.get_texture(cur_s, x, y, 3).unwrap_or(0);
+    if d <= 0 {
+        return None;
+    }
+    d_data
+        .event(cur_s, d as usize, trigger)
+        .map(|ev| ((d as usize, x, y), ev))
+}
+
+// the tile the player faces
+fn faced(sta: &SceneStatus) -> Option<(i32, i32)> {
+    let (dx, dy) = sta.pos.facing.as_ref()?.pos();
+    Some((sta.pos.x as i32 + dx, sta.pos.y as i32 + dy))
+}
+
 pub fn on_event(
-    mut commands: Commands,
-    mut events: ResMut<Events<JyEvent>>,
+    mut runtime: ResMut<ScriptRuntime>,
+    asset_server: Res<AssetServer>,
     keyboard_input: ResMut<Input<KeyCode>>,
     d_data: Res<DData>,
     s_data: Res<SData>,
     mut sta: ResMut<SceneStatus>,
     mut state: ResMut<State<GameState>>,
+    mut steps: EventReader<Stepped>,
+    item_menu: Option<Res<ItemMenu>>,
 ) {
     if sta.is_new_game {
-        script::execute_n(&mut commands, &mut state, &mut events, 691);
+        script::execute_n(&mut state, &mut runtime, &asset_server, 691);
         sta.is_new_game = false;
         sta.pos.facing = Some(MoveDir::Up);
         return;
     }
 
-    if keyboard_input.just_pressed(KeyCode::Space) && sta.pos.facing.is_some() {
-        let next_x = sta.pos.x as i32 + sta.pos.facing().pos().0;
-        let next_y = sta.pos.y as i32 + sta.pos.facing().pos().1;
-        let d = s_data.get_texture(sta.cur_s as usize, next_x as usize, next_y as usize, 3);
-        if d > 0 {
-            sta.cur_d = (d as usize, next_x as usize, next_y as usize);
-            let ev = d_data.get_d(sta.cur_s as usize, d as usize, 2);
-            if ev > 0 {
-                script::execute_n(&mut commands, &mut state, &mut events, ev as i16);
-            }
+    let stepped = steps.iter().last().map(|v| (v.0 as i32, v.1 as i32));
+    if item_menu.is_some() {
+        return;
+    }
+    let found = match (faced(&sta), stepped) {
+        (Some(pos), _) if keyboard_input.just_pressed(KeyCode::Space) => {
+            event_at(&d_data, &s_data, sta.cur_s, pos, Trigger::Talk)
+        }
+        (_, Some(pos)) => event_at(&d_data, &s_data, sta.cur_s, pos, Trigger::Step),
+        _ => None,
+    };
+    if let Some((cur_d, ev)) = found {
+        sta.cur_d = cur_d;
+        runtime.current_thing = None;
+        script::execute_n(&mut state, &mut runtime, &asset_server, ev);
+    }
+}
+
+// E lists the things in the backpack to use one on the faced tile
+fn open_item_menu(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    backpack: Res<Backpack>,
+    things: Res<Vec<structs::Thing>>,
+    encoding: Res<TextEncoding>,
+    item_menu: Option<Res<ItemMenu>>,
+) {
+    if item_menu.is_some() || !keyboard_input.just_pressed(KeyCode::E) {
+        return;
+    }
+    keyboard_input.clear();
+    let (ids, names) = backpack
+        .items
+        .iter()
+        .map(|(id, num)| {
+            let name = things.get(*id as usize).map(|v| v.name(*encoding)).unwrap_or_default();
+            (*id, format!("{} {}", name, num))
+        })
+        .unzip();
+    spawn_choice(&mut commands, &asset_server, "使用物品".into(), Choice::new(names));
+    commands.insert_resource(ItemMenu(ids));
+}
+
+// runs the item event of the faced tile with the picked thing
+fn use_item_menu(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    item_menu: Option<Res<ItemMenu>>,
+    choice: Option<Res<Choice>>,
+    mut rows: Query<(&ChoiceRow, &mut Text)>,
+    boxes: Query<Entity, With<DialogBox>>,
+    mut runtime: ResMut<ScriptRuntime>,
+    asset_server: Res<AssetServer>,
+    d_data: Res<DData>,
+    s_data: Res<SData>,
+    mut sta: ResMut<SceneStatus>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let (item_menu, choice) = match (item_menu, choice) {
+        (Some(item_menu), Some(choice)) => (item_menu, choice),
+        _ => return,
+    };
+    let picked = match choice.update(&mut commands, &mut keyboard_input, &mut rows) {
+        Some(v) => v,
+        None => return,
+    };
+    for entity in boxes.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<ItemMenu>();
+
+    let thing = match picked.and_then(|v| item_menu.0.get(v)) {
+        Some(v) => *v,
+        None => return,
+    };
+    let found =
+        faced(&sta).and_then(|pos| event_at(&d_data, &s_data, sta.cur_s, pos, Trigger::Item));
+    match found {
+        Some((cur_d, ev)) => {
+            sta.cur_d = cur_d;
+            runtime.current_thing = Some(thing);
+            script::execute_n(&mut state, &mut runtime, &asset_server, ev);
         }
+        None => debug!("nothing to use thing {} on", thing),
     }
 }
 
@@ -245,7 +392,13 @@ pub fn movement(
     keyboard_input: ResMut<Input<KeyCode>>,
     mut query: Query<&mut Transform, (With<NetCell>, Without<Me>)>,
     mut me_query: Query<(&mut SpriteMeta, &mut TextureAtlasSprite), With<Me>>,
+    mut steps: EventWriter<Stepped>,
+    item_menu: Option<Res<ItemMenu>>,
 ) {
+    // the arrows pick a thing meanwhile
+    if item_menu.is_some() {
+        return;
+    }
     let mut moves = HashMap::new();
     moves.insert(KeyCode::Up, MoveDir::Up);
     moves.insert(KeyCode::Down, MoveDir::Down);
@@ -285,18 +438,25 @@ pub fn movement(
             let next_x = sta.pos.x + dir.pos().0 as f32;
             let next_y = sta.pos.y + dir.pos().1 as f32;
 
-            if s_data.get_texture(sta.cur_s as usize, next_x as usize, next_y as usize, 1) > 0 {
+            // a building or the edge of the scene blocks the way
+            if s_data
+                .get_texture(sta.cur_s as usize, next_x as usize, next_y as usize, 1)
+                .map_or(true, |v| v > 0)
+            {
                 return ControlFlow::Break(());
             }
 
-            let d = s_data.get_texture(sta.cur_s as usize, next_x as usize, next_y as usize, 3);
+            let d = s_data
+                .get_texture(sta.cur_s as usize, next_x as usize, next_y as usize, 3)
+                .unwrap_or(0);
             if d > 0 {
-                if d_data.get_d(sta.cur_s as usize, d as usize, 0) > 0 {
+                if d_data.get_d(sta.cur_s as usize, d as usize, 0).unwrap_or(0) > 0 {
                     return ControlFlow::Break(());
                 }
             }
 
             sta.pos.update(next_x as usize, next_y as usize);
+            steps.send(Stepped(next_x as usize, next_y as usize));
             for mut iter in query.iter_mut() {
                 iter.translation.x += dir.offset().0 * XSCALE;
                 iter.translation.y += dir.offset().1 * YSCALE;
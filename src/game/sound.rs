@@ -5,13 +5,24 @@ pub struct Plugin;
 
 impl bevy::prelude::Plugin for Plugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(play_audio);
+        app.add_event::<SoundEffect>()
+            .add_startup_system(play_audio)
+            .add_system(play_effects);
     }
 }
 
+/// A sound of org/sound played over the music, like `atk01.wav`.
+pub struct SoundEffect(pub String);
+
 fn play_audio(_commands: Commands, res: Res<AssetServer>, mut ew: EventWriter<PlayEvent>) {
     let cur = "music/game01.mp3";
     let sound = res.load::<AudioSource, &'static str>(cur);
     ew.send(PlayEvent::Loop(true));
     ew.send(PlayEvent::Append(sound));
 }
+
+fn play_effects(res: Res<AssetServer>, audio: Res<Audio>, mut effects: EventReader<SoundEffect>) {
+    for effect in effects.iter() {
+        audio.play(res.load(format!("org/sound/{}", effect.0).as_str()));
+    }
+}
@@ -6,6 +6,8 @@ use bevy::app::AppExit;
 use bevy::prelude::*;
 use jy_derive::JyMenu;
 
+use crate::game::load::GameSlot;
+use crate::game::save::slot_exists;
 use crate::game::util::despawn_screen;
 use crate::game::{is_splash, GameStage, GameState, Menu};
 
@@ -33,9 +35,9 @@ enum MainOption {
     Exit,
 }
 
+// R1 - R3, slot 0 is the data of a new game
 #[derive(Clone, JyMenu)]
 enum SecondOption {
-    Slot0,
     Slot1,
     Slot2,
     Slot3,
@@ -134,11 +136,12 @@ fn keyboard_input_system(
                 app_exit_events.send(AppExit);
             }
             MainOption::Start => {
+                commands.insert_resource(GameSlot::NewGame);
                 state.set(SplashState::End).unwrap();
                 game_state.set(GameState::Load).unwrap();
             }
             MainOption::Load => {
-                commands.insert_resource(SecondOption::Slot0);
+                commands.insert_resource(SecondOption::Slot1);
                 state.set(SplashState::Loading).unwrap();
             }
         }
@@ -262,13 +265,24 @@ fn setup_load_options(mut commands: Commands, asset_server: Res<AssetServer>) {
         });
 }
 
-/// This system prints 'A' key state
+/// Loads the chosen slot, or goes back to the main menu when it has no save yet
 fn keyboard_input_sub_system(
+    mut commands: Commands,
     mut keyboard_input: ResMut<Input<KeyCode>>,
     mut state: ResMut<State<SplashState>>,
+    mut game_state: ResMut<State<GameState>>,
+    options: Res<SecondOption>,
 ) {
     if keyboard_input.just_released(KeyCode::Return) {
         keyboard_input.clear();
-        state.set(SplashState::Init).unwrap();
+        let slot = options.to_idx() + 1;
+        if !slot_exists(slot) {
+            warn!("no save in slot {}", slot);
+            state.set(SplashState::Init).unwrap();
+            return;
+        }
+        commands.insert_resource(GameSlot::Saved(slot));
+        state.set(SplashState::End).unwrap();
+        game_state.set(GameState::Load).unwrap();
     }
 }
@@ -1,17 +1,19 @@
 #![allow(dead_code)]
 
-use std::io::{Cursor, Read};
+use std::io::Cursor;
+use std::slice;
 use std::sync::Mutex;
-use std::{mem, slice};
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt};
+use jy_derive::JyRecord;
 use lazy_static::lazy_static;
 
 use bevy::prelude::{Image, KeyCode};
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
 
+use crate::game::encoding::{self, TextEncoding};
 use crate::game::util::PosXY;
-use crate::game::GrpAsset;
+use crate::game::{GrpAsset, JyDataError};
 use crate::read;
 
 lazy_static! {
@@ -47,6 +49,13 @@ pub const MMAP_HEIGH: usize = 480;
 
 pub const LAYER_NUM: usize = 6;
 
+// CC.WarWidth=64;   --战斗地图大小
+pub const WAR_WIDTH: usize = 64;
+// CC.WarHeight=64;
+pub const WAR_HEIGHT: usize = 64;
+// earth and building
+pub const WAR_LAYER_NUM: usize = 2;
+
 // CONFIG.XSCALE = 18    -- 贴图宽度的一半
 pub const XSCALE: f32 = 18.;
 // CONFIG.YSCALE = 9     -- 贴图高度的一半
@@ -60,16 +69,18 @@ CC.TEAM_NUM=6;          --队伍人数
 CC.MY_THING_NUM=200      --主角物品数量
  */
 const TEAM_NUM: usize = 6;
-const MY_THING_NUM: usize = 200;
-const ACTION_FRAME: usize = 5;
+pub const MY_THING_NUM: usize = 200;
+pub const ACTION_FRAME: usize = 5;
 const SKILL_NUM: usize = 10;
 const ITEM_NUM: usize = 4;
-
-const PERSON_SIZE: usize = mem::size_of::<Person>();
-const BASE_SIZE: usize = mem::size_of::<Base>();
-const SCENE_SIZE: usize = mem::size_of::<Scene>();
-
-#[derive(Debug)]
+const WUGONG_LEVEL: usize = 10;
+const SHOP_ITEM: usize = 5;
+// CC.WarData_S, the allies and the enemies of a battle
+const WAR_ALLY_NUM: usize = 6;
+const WAR_ENEMY_NUM: usize = 20;
+
+#[derive(Debug, JyRecord)]
+#[jy(size = 836)]
 pub struct Base {
     // CC.Base_S["乘船"] ={0, 0, 2} - - 起始位置(从0开始)，数据类型(0有符号 1无符号，2字符串)，长度
     pub boat: i16,
@@ -101,6 +112,7 @@ pub struct Base {
     CC.Base_S["队伍"..i]={24 + 2 * (i - 1),0, 2};
     end
      */
+    #[jy(offset = 24)]
     pub teams: [i16; TEAM_NUM],
     /*
     for i = 1,
@@ -109,6 +121,7 @@ pub struct Base {
     CC.Base_S["物品数量"..i]={36 + 4 * (i - 1) +2, 0, 2};
     end
      */
+    #[jy(offset = 36)]
     pub items: [(i16, i16); MY_THING_NUM],
 }
 
@@ -117,13 +130,44 @@ pub struct Backpack {
     pub items: Vec<(i16, i16)>,
 }
 
-#[derive(Debug)]
+impl Backpack {
+    pub fn new(base: &Base) -> Self {
+        Backpack {
+            items: base
+                .items
+                .iter()
+                .filter(|(item, _)| *item >= 0)
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Adds `num` of a thing, a new one is dropped once the `MY_THING_NUM` slots
+    /// of `Base::items` are taken, as instruct_32 of the original does.
+    pub fn add(&mut self, thing: i16, num: i16) {
+        match self.items.iter().position(|(item, _)| *item == thing) {
+            None if self.items.len() < MY_THING_NUM => self.items.push((thing, num)),
+            None => {}
+            Some(idx) => {
+                self.items[idx].1 += num;
+                if self.items[idx].1 <= 0 {
+                    self.items.remove(idx);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, JyRecord)]
+#[jy(size = 62)]
 pub struct Scene {
     // CC.Scene_S["代号"]={0,0,2}
     pub code: i16,
     // CC.Scene_S["名称"]={2,2,20}
+    #[jy(offset = 2)]
     pub name: [u8; 20],
     // CC.Scene_S["出门音乐"]={22,0,2}
+    #[jy(offset = 22)]
     pub leave_music: i16,
     // CC.Scene_S["进门音乐"]={24,0,2}
     pub enter_music: i16,
@@ -166,7 +210,8 @@ pub struct Scene {
 }
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, JyRecord)]
+#[jy(size = 202)]
 pub struct Person {
     // CC.Person_S["代号"]={0,0,2}
     code: i16,
@@ -177,10 +222,13 @@ pub struct Person {
     // CC.Person_S["无用"]={6,0,2}
     useless: i16,
     // CC.Person_S["姓名"]={8,2,20}
+    #[jy(offset = 8)]
     name: [u8; 20],
     // CC.Person_S["外号"]={28,2,20}
+    #[jy(offset = 28)]
     alias: [u8; 20],
     // CC.Person_S["性别"]={48,0,2}
+    #[jy(offset = 48)]
     male: i16,
     // CC.Person_S["等级"]={50,0,2}
     level: i16,
@@ -208,10 +256,14 @@ pub struct Person {
     // CC.Person_S["出招动画延迟" .. i]={80+2*(i-1),0,2};
     // CC.Person_S["武功音效延迟" .. i]={90+2*(i-1),0,2};
     // end
+    #[jy(offset = 70)]
     action_frames: [i16; ACTION_FRAME],
+    #[jy(offset = 80)]
     action_delays: [i16; ACTION_FRAME],
+    #[jy(offset = 90)]
     action_audio_delays: [i16; ACTION_FRAME],
     // CC.Person_S["内力性质"]={100,0,2}
+    #[jy(offset = 100)]
     neili_status: i16,
     // CC.Person_S["内力"]={102,0,2}
     neili: i16,
@@ -264,7 +316,9 @@ pub struct Person {
     CC.Person_S["武功等级"..i]={166 + 2 * (i - 1),0, 2};
     end
      */
+    #[jy(offset = 146)]
     skills: [i16; SKILL_NUM],
+    #[jy(offset = 166)]
     skill_levels: [i16; SKILL_NUM],
     /*
     for i = 1,
@@ -273,228 +327,272 @@ pub struct Person {
     CC.Person_S["携带物品数量"..i]={194 + 2 * (i - 1),0, 2};
     end
      */
+    #[jy(offset = 186)]
     items: [i16; ITEM_NUM],
+    #[jy(offset = 194)]
     item_nums: [i16; ITEM_NUM],
 }
 
-fn to_str(v: &[u8]) -> String {
-    let out = v
-        .iter()
-        .map_while(|v| if *v == 0 { None } else { Some(*v) })
-        .collect::<Vec<u8>>();
-    String::from_utf8(out).unwrap()
-}
-
 pub fn rbg2rgba(c: u32) -> u32 {
     ((c & 0xFF) << 16) + (c & 0xFF00) + ((c & 0xFF0000) >> 16) + 0xFF000000
 }
 
 impl Person {
-    pub fn new(data: &[u8]) -> Self {
-        let mut c = std::io::Cursor::new(data);
-        let code = read!(c, i16);
-        let avatar = read!(c, i16);
-        let life_gain = read!(c, i16);
-        let useless = read!(c, i16);
-        let mut name = [0; 20];
-        c.read(&mut name).unwrap();
-        let mut alias = [0; 20];
-        c.read(&mut alias).unwrap();
-        let male = read!(c, i16);
-        let level = read!(c, i16);
-        let exp = read!(c, u16);
-        let life = read!(c, i16);
-        let life_max = read!(c, i16);
-        let injure = read!(c, i16);
-        let tox = read!(c, i16);
-        let vatity = read!(c, i16);
-        let item_familiar = read!(c, i16);
-        let weapon = read!(c, i16);
-        let armor = read!(c, i16);
-        let action_frames = [0; ACTION_FRAME].map(|_| read!(c, i16));
-        let action_delays = [0; ACTION_FRAME].map(|_| read!(c, i16));
-        let action_audio_delays = [0; ACTION_FRAME].map(|_| read!(c, i16));
-        let neili_status = read!(c, i16);
-        let neili = read!(c, i16);
-        let neili_max = read!(c, i16);
-        let attack = read!(c, i16);
-        let agile = read!(c, i16);
-        let defence = read!(c, i16);
-        let cure = read!(c, i16);
-        let poison = read!(c, i16);
-        let depoison = read!(c, i16);
-        let poison_def = read!(c, i16);
-        let fist = read!(c, i16);
-        let sword = read!(c, i16);
-        let knife = read!(c, i16);
-        let other_weapon = read!(c, i16);
-        let fly_weapon = read!(c, i16);
-        let wknowlege = read!(c, i16);
-        let sanity = read!(c, i16);
-        let with_poison = read!(c, i16);
-        let double_att = read!(c, i16);
-        let reputation = read!(c, i16);
-        let talent = read!(c, i16);
-        let item_train = read!(c, i16);
-        let item_point = read!(c, i16);
-        let skills = [0; SKILL_NUM].map(|_| read!(c, i16));
-        let skill_levels = [0; SKILL_NUM].map(|_| read!(c, i16));
-        let items = [0; ITEM_NUM].map(|_| read!(c, i16));
-        let item_nums = [0; ITEM_NUM].map(|_| read!(c, i16));
-        Person {
-            code,
-            avatar,
-            life_gain,
-            useless,
-            name,
-            alias,
-            male,
-            level,
-            exp,
-            life,
-            life_max,
-            injure,
-            tox,
-            vatity,
-            item_familiar,
-            weapon,
-            armor,
-            action_frames,
-            action_delays,
-            action_audio_delays,
-            neili_status,
-            neili,
-            neili_max,
-            attack,
-            agile,
-            defence,
-            cure,
-            poison,
-            depoison,
-            poison_def,
-            fist,
-            sword,
-            knife,
-            other_weapon,
-            fly_weapon,
-            wknowlege,
-            sanity,
-            with_poison,
-            double_att,
-            reputation,
-            talent,
-            item_train,
-            item_point,
-            skills,
-            skill_levels,
-            items,
-            item_nums,
-        }
+    pub fn name(&self, encoding: TextEncoding) -> String {
+        encoding::decode(encoding, &self.name)
     }
 
-    pub fn name(&self) -> String {
-        to_str(&self.name)
+    pub fn alias(&self, encoding: TextEncoding) -> String {
+        encoding::decode(encoding, &self.alias)
     }
 
-    pub fn alias(&self) -> String {
-        to_str(&self.alias)
+    pub fn set_name(&mut self, encoding: TextEncoding, name: &str) {
+        encoding::encode_into(encoding, name, &mut self.name);
     }
-}
 
-impl Base {
-    pub fn new(data: &[u8]) -> Self {
-        let mut c = std::io::Cursor::new(data);
-        Base {
-            boat: read!(c, i16),
-            useless: read!(c, i16),
-            person_x: read!(c, i16),
-            person_y: read!(c, i16),
-            person_x_scene: read!(c, i16),
-            person_y_scene: read!(c, i16),
-            person_dir: read!(c, i16),
-            boat_x: read!(c, i16),
-            boat_y: read!(c, i16),
-            boat_x1: read!(c, i16),
-            boat_y1: read!(c, i16),
-            boat_dir: read!(c, i16),
-            teams: [0; TEAM_NUM].map(|_| read!(c, i16)),
-            items: [0; MY_THING_NUM].map(|_| (read!(c, i16), read!(c, i16))),
-        }
+    pub fn level(&self) -> i16 {
+        self.level
+    }
+
+    pub fn avatar(&self) -> i16 {
+        self.avatar
+    }
+
+    pub fn exp(&self) -> u16 {
+        self.exp
+    }
+
+    pub fn add_exp(&mut self, exp: u16) {
+        self.exp = self.exp.saturating_add(exp);
+    }
+
+    pub fn life(&self) -> i16 {
+        self.life
+    }
+
+    pub fn set_life(&mut self, life: i16) {
+        self.life = life.min(self.life_max).max(0);
+    }
+
+    pub fn life_max(&self) -> i16 {
+        self.life_max
+    }
+
+    pub fn injure(&self) -> i16 {
+        self.injure
+    }
+
+    pub fn set_injure(&mut self, injure: i16) {
+        self.injure = injure.clamp(0, 100);
+    }
+
+    pub fn tox(&self) -> i16 {
+        self.tox
+    }
+
+    pub fn set_tox(&mut self, tox: i16) {
+        self.tox = tox.clamp(0, 100);
+    }
+
+    pub fn vatity(&self) -> i16 {
+        self.vatity
+    }
+
+    /// 出招动画帧数 of an attack kind, the wugong type less one.
+    pub fn action_frames(&self, kind: usize) -> i16 {
+        self.action_frames[kind]
+    }
+
+    /// 出招动画延迟 of an attack kind.
+    pub fn action_delay(&self, kind: usize) -> i16 {
+        self.action_delays[kind]
+    }
+
+    /// 武功音效延迟 of an attack kind, the frame the sound plays at.
+    pub fn action_audio_delay(&self, kind: usize) -> i16 {
+        self.action_audio_delays[kind]
+    }
+
+    pub fn weapon(&self) -> i16 {
+        self.weapon
+    }
+
+    pub fn armor(&self) -> i16 {
+        self.armor
+    }
+
+    pub fn neili(&self) -> i16 {
+        self.neili
+    }
+
+    pub fn set_neili(&mut self, neili: i16) {
+        self.neili = neili.min(self.neili_max).max(0);
+    }
+
+    pub fn neili_max(&self) -> i16 {
+        self.neili_max
+    }
+
+    pub fn attack(&self) -> i16 {
+        self.attack
+    }
+
+    pub fn agile(&self) -> i16 {
+        self.agile
+    }
+
+    pub fn defence(&self) -> i16 {
+        self.defence
+    }
+
+    pub fn poison_def(&self) -> i16 {
+        self.poison_def
+    }
+
+    pub fn with_poison(&self) -> i16 {
+        self.with_poison
+    }
+
+    /// The learned skills as (wugong id, 武功等级), empty slots are skipped.
+    pub fn skills(&self) -> impl Iterator<Item = (usize, i16)> + '_ {
+        self.skills
+            .iter()
+            .zip(self.skill_levels.iter())
+            .filter(|(id, _)| **id > 0)
+            .map(|(id, level)| (*id as usize, *level))
+    }
+
+    /// The carried things as (thing id, count), empty slots are skipped.
+    pub fn items(&self) -> impl Iterator<Item = (usize, i16)> + '_ {
+        self.items
+            .iter()
+            .zip(self.item_nums.iter())
+            .filter(|(id, num)| **id >= 0 && **num > 0)
+            .map(|(id, num)| (*id as usize, *num))
+    }
+
+    /// The learned skills resolved against the wugong records, with the table index of their level.
+    pub fn wugongs<'a>(
+        &'a self,
+        wugongs: &'a [Wugong],
+    ) -> impl Iterator<Item = (&'a Wugong, usize)> + 'a {
+        self.skills()
+            .filter_map(move |(id, level)| wugongs.get(id).map(|v| (v, Wugong::level_of(level))))
     }
 }
 
 impl Scene {
-    pub fn new(data: &[u8]) -> Self {
-        let mut c = std::io::Cursor::new(data);
-        Scene {
-            code: read!(c, i16),
-            name: {
-                let mut name = [0; 20];
-                c.read(&mut name).unwrap();
-                name
-            },
-            leave_music: read!(c, i16),
-            enter_music: read!(c, i16),
-            jump: read!(c, i16),
-            enter_pre: read!(c, i16),
-            out_entry_x1: read!(c, i16),
-            out_entry_y1: read!(c, i16),
-            out_entry_x2: read!(c, i16),
-            out_entry_y2: read!(c, i16),
-            entry_x: read!(c, i16),
-            entry_y: read!(c, i16),
-            exit_x1: read!(c, i16),
-            exit_x2: read!(c, i16),
-            exit_x3: read!(c, i16),
-            exit_y1: read!(c, i16),
-            exit_y2: read!(c, i16),
-            exit_y3: read!(c, i16),
-            jump_x1: read!(c, i16),
-            jump_y1: read!(c, i16),
-            jump_x2: read!(c, i16),
-            jump_y2: read!(c, i16),
-        }
+    pub fn name(&self, encoding: TextEncoding) -> String {
+        encoding::decode(encoding, &self.name)
     }
+}
 
-    pub fn name(&self) -> String {
-        to_str(&self.name)
-    }
+// the fields of every D* event
+pub const D_FIELDS: usize = 11;
+
+/// What sets off the events of a D* record, by the field they are in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    // Space facing the tile
+    Talk = 2,
+    // a thing used on the faced tile
+    Item = 3,
+    // walking onto the tile
+    Step = 4,
 }
+pub const SCENE_MAP_BYTES: usize = LAYER_NUM * SCENE_WIDTH * SCENE_HEIGHT * 2;
+pub const SCENE_EVENT_BYTES: usize = DNUM * D_FIELDS * 2;
 
 pub struct SData(Vec<u8>);
 
 impl SData {
-    pub fn new(gs: GrpAsset) -> Self {
-        SData(gs.data)
+    pub fn new(gs: GrpAsset, scene_num: usize) -> Result<Self, JyDataError> {
+        if gs.data.len() < scene_num * SCENE_MAP_BYTES {
+            return Err(JyDataError::Truncated {
+                what: "scene map data",
+                need: scene_num * SCENE_MAP_BYTES,
+                got: gs.data.len(),
+            });
+        }
+        Ok(SData(gs.data))
     }
 
-    pub fn get_texture(&self, scene_id: usize, w: usize, h: usize, layer: usize) -> i16 {
+    pub fn get_texture(
+        &self,
+        scene_id: usize,
+        w: usize,
+        h: usize,
+        layer: usize,
+    ) -> Result<i16, JyDataError> {
+        check_range("scene x", w, SCENE_WIDTH)?;
+        check_range("scene y", h, SCENE_HEIGHT)?;
+        check_range("scene layer", layer, LAYER_NUM)?;
+        check_range("scene", scene_id, self.0.len() / SCENE_MAP_BYTES)?;
         let i = (scene_id * LAYER_NUM + layer) * SCENE_WIDTH * SCENE_HEIGHT + h * SCENE_WIDTH + w;
         let mut data = &self.0[i * 2..];
-        data.read_i16::<LittleEndian>().unwrap()
+        Ok(read!(data, i16))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
     }
 }
 
 pub struct DData(Vec<u8>);
 
 impl DData {
-    pub fn new(gs: GrpAsset) -> Self {
-        DData(gs.data)
+    pub fn new(gs: GrpAsset, scene_num: usize) -> Result<Self, JyDataError> {
+        if gs.data.len() < scene_num * SCENE_EVENT_BYTES {
+            return Err(JyDataError::Truncated {
+                what: "scene event data",
+                need: scene_num * SCENE_EVENT_BYTES,
+                got: gs.data.len(),
+            });
+        }
+        Ok(DData(gs.data))
     }
 
-    pub fn get_d(&self, scene_id: usize, id: usize, i: usize) -> i16 {
-        let i = (scene_id * DNUM + id) * 11 + i;
-        let mut data = &self.0[i * 2..];
-        data.read_i16::<LittleEndian>().unwrap()
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn offset(&self, scene_id: usize, id: usize, i: usize) -> Result<usize, JyDataError> {
+        check_range("event", id, DNUM)?;
+        check_range("event field", i, D_FIELDS)?;
+        check_range("scene", scene_id, self.0.len() / SCENE_EVENT_BYTES)?;
+        Ok(((scene_id * DNUM + id) * D_FIELDS + i) * 2)
     }
 
-    pub fn set(&mut self, scene_id: usize, id: usize, i: usize, val: i16) {
-        let i = (scene_id * DNUM + id) * 11 + i;
-        let data = &mut self.0[i * 2..i * 2 + 2];
-        let mut test = vec![];
-        test.write_i16::<LittleEndian>(val).unwrap();
-        data[0] = test[0];
-        data[1] = test[1];
+    pub fn get_d(&self, scene_id: usize, id: usize, i: usize) -> Result<i16, JyDataError> {
+        let mut data = &self.0[self.offset(scene_id, id, i)?..];
+        Ok(read!(data, i16))
+    }
+
+    /// The event `trigger` starts on the record, None when it has none.
+    pub fn event(&self, scene_id: usize, id: usize, trigger: Trigger) -> Option<i16> {
+        self.get_d(scene_id, id, trigger as usize)
+            .ok()
+            .filter(|v| *v > 0)
+    }
+
+    pub fn set(
+        &mut self,
+        scene_id: usize,
+        id: usize,
+        i: usize,
+        val: i16,
+    ) -> Result<(), JyDataError> {
+        let i = self.offset(scene_id, id, i)?;
+        self.0[i..i + 2].copy_from_slice(&val.to_le_bytes());
+        Ok(())
+    }
+}
+
+fn check_range(what: &'static str, index: usize, len: usize) -> Result<(), JyDataError> {
+    if index < len {
+        Ok(())
+    } else {
+        Err(JyDataError::OutOfRange { what, index, len })
     }
 }
 
@@ -506,10 +604,16 @@ pub struct TextureMap {
 pub enum MapType {
     Smap,
     Mmap,
+    // head portraits of hdgrp.grp
+    Head,
+    // the tiles of the battle maps, wmap.grp
+    Wmap,
 }
 
 pub struct SMapTexture(pub TextureMap);
 pub struct MMapTexture(pub TextureMap);
+pub struct HeadTexture(pub TextureMap);
+pub struct WMapTexture(pub TextureMap);
 
 fn parse(
     buf: &mut Vec<u32>,
@@ -554,7 +658,9 @@ fn parse(
                     }
                      */
                     if let Some(v) = buf.get_mut(_idx as usize) {
-                        let c = *colors.get(c as usize).unwrap();
+                        let c = *colors
+                            .get(c as usize)
+                            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
                         *v = c;
                         _idx += 1;
                     }
@@ -579,12 +685,23 @@ impl TextureMap {
         TextureMap { gs }
     }
 
-    pub fn get_image(&self, id: usize, colors: &Vec<u32>) -> Option<(Image, TextureMeta)> {
-        let maybe_data = self.gs.idx(id as usize);
-        if maybe_data.is_none() {
-            return None;
+    /// Decodes picture `id`, an empty idx entry is no picture.
+    pub fn get_image(
+        &self,
+        id: usize,
+        colors: &Vec<u32>,
+    ) -> Result<Option<(Image, TextureMeta)>, JyDataError> {
+        let data = self.gs.idx(id)?;
+        if data.is_empty() {
+            return Ok(None);
+        }
+        if data.len() < 8 {
+            return Err(JyDataError::Truncated {
+                what: "picture header",
+                need: 8,
+                got: data.len(),
+            });
         }
-        let data = maybe_data.unwrap();
         let mut c = Cursor::new(data);
         let w = read!(c, u16);
         let h = read!(c, u16);
@@ -601,10 +718,17 @@ impl TextureMap {
             );
         }
 
+        if w == 0 || h == 0 {
+            return Ok(None);
+        }
+
         let mut decode_buf: Vec<u32> = vec![];
-        decode_buf.resize((w * h) as usize, 0x0);
+        decode_buf.resize(w as usize * h as usize, 0x0);
 
-        parse(&mut decode_buf, w as usize, &mut c, colors).unwrap();
+        parse(&mut decode_buf, w as usize, &mut c, colors).map_err(|_| JyDataError::Corrupt {
+            what: "picture",
+            index: id,
+        })?;
         let pixel_ptr = decode_buf.as_ptr() as *const u8;
         let pixel = unsafe { slice::from_raw_parts(pixel_ptr, decode_buf.len() * 4) };
 
@@ -619,7 +743,60 @@ impl TextureMap {
             TextureFormat::Rgba8UnormSrgb,
         );
 
-        Some((image, TextureMeta(w, h, xoff as f32, yoff as f32)))
+        Ok(Some((image, TextureMeta(w, h, xoff as f32, yoff as f32))))
+    }
+}
+
+/// The dialogue texts of talk.grp, every byte is stored inverted and `*` breaks the line.
+pub struct Talks(pub GrpAsset);
+
+impl Talks {
+    pub fn get(&self, talk_id: usize, encoding: TextEncoding) -> Result<String, JyDataError> {
+        let data = self.0.idx(talk_id)?;
+        let text = data.iter().map(|v| v ^ 0xFF).collect::<Vec<u8>>();
+        Ok(encoding::decode(encoding, &text).replace('*', "\n"))
+    }
+}
+
+/// The original event scripts, kdef.grp entry N is the event `oldevent_N`.
+pub struct Kdef(pub GrpAsset);
+
+impl Kdef {
+    /// The instruction words of an event.
+    pub fn get(&self, event_id: usize) -> Result<Vec<i16>, JyDataError> {
+        let data = self.0.idx(event_id)?;
+        let mut c = Cursor::new(data);
+        Ok((0..data.len() / 2).map(|_| read!(c, i16)).collect())
+    }
+}
+
+pub const WAR_MAP_BYTES: usize = WAR_LAYER_NUM * WAR_WIDTH * WAR_HEIGHT * 2;
+
+/// The battle maps of warfld.grp, an entry is the earth and the building layer of one map.
+pub struct WarFld(pub GrpAsset);
+
+impl WarFld {
+    pub fn get_texture(
+        &self,
+        map: usize,
+        x: usize,
+        y: usize,
+        layer: usize,
+    ) -> Result<i16, JyDataError> {
+        check_range("battle x", x, WAR_WIDTH)?;
+        check_range("battle y", y, WAR_HEIGHT)?;
+        check_range("battle layer", layer, WAR_LAYER_NUM)?;
+        let data = self.0.idx(map)?;
+        if data.len() < WAR_MAP_BYTES {
+            return Err(JyDataError::Truncated {
+                what: "battle map",
+                need: WAR_MAP_BYTES,
+                got: data.len(),
+            });
+        }
+        let i = (layer * WAR_HEIGHT + y) * WAR_WIDTH + x;
+        let mut data = &data[i * 2..];
+        Ok(read!(data, i16))
     }
 }
 
@@ -662,17 +839,22 @@ pub fn load_i16(data: &[u8]) -> Vec<i16> {
 }
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, JyRecord)]
+#[jy(size = 260)]
 pub struct Thing {
     // CC.Thing_S["代号"]={0,0,2}
     code: i16,
     // CC.Thing_S["名称"]={2,2,40}
+    #[jy(offset = 2)]
     name: [u8; 40],
     // CC.Thing_S["名称2"]={42,2,40}
+    #[jy(offset = 42)]
     name2: [u8; 40],
     // CC.i16_S["物品说明"]={82,2,60}
+    #[jy(offset = 82)]
     desp: [u8; 60],
     // CC.Thing_S["练出武功"]={142,0,2}
+    #[jy(offset = 142)]
     wugong: i16,
     // CC.Thing_S["暗器动画编号"]={144,0,2}
     animate_code: i16,
@@ -770,150 +952,286 @@ pub struct Thing {
     item_exp: i16,
     // CC.Thing_S["需材料"]={238,0,2}
     meterial_req: i16,
+    // for i=1,5 do
+    // CC.Thing_S["练出物品" .. i]={240+2*(i-1),0,2};
+    // CC.Thing_S["需要物品数量" .. i]={250+2*(i-1),0,2};
+    // end
+    #[jy(offset = 240)]
     items: [i16; 5],
+    #[jy(offset = 250)]
     item_reqs: [i16; 5],
 }
 
 impl Thing {
-    pub fn new(data: &[u8]) -> Self {
-        let mut c = std::io::Cursor::new(data);
-        let code = read!(c, i16);
-        let mut name = [0; 40];
-        c.read(&mut name).unwrap();
-        let mut name2 = [0; 40];
-        c.read(&mut name2).unwrap();
-        let mut desp = [0; 60];
-        c.read(&mut desp).unwrap();
-        let wugong = read!(c, i16);
-        let animate_code = read!(c, i16);
-        let user = read!(c, i16);
-        let equip_type = read!(c, i16);
-        let show = read!(c, i16);
-        let typ = read!(c, i16);
-        let unknown5 = read!(c, i16);
-        let unknown6 = read!(c, i16);
-        let unknown7 = read!(c, i16);
-        let add_life = read!(c, i16);
-        let add_max_life = read!(c, i16);
-        let add_detox = read!(c, i16);
-        let add_vat = read!(c, i16);
-        let change_mag = read!(c, i16);
-        let add_mag = read!(c, i16);
-        let add_max_mag = read!(c, i16);
-        let add_attack = read!(c, i16);
-        let add_agile = read!(c, i16);
-        let add_def = read!(c, i16);
-        let add_heal = read!(c, i16);
-        let add_poison = read!(c, i16);
-        let add_depoison = read!(c, i16);
-        let add_anti_poi = read!(c, i16);
-        let add_fist = read!(c, i16);
-        let add_sword = read!(c, i16);
-        let add_knife = read!(c, i16);
-        let add_special_weapon = read!(c, i16);
-        let add_arrow = read!(c, i16);
-        let add_know = read!(c, i16);
-        let add_rep = read!(c, i16);
-        let add_attack_time = read!(c, i16);
-        let add_attack_poi = read!(c, i16);
-        let use_only = read!(c, i16);
-        let magic_type = read!(c, i16);
-        let magic_req = read!(c, i16);
-        let att_req = read!(c, i16);
-        let agile_req = read!(c, i16);
-        let poi_req = read!(c, i16);
-        let heal_req = read!(c, i16);
-        let depoi_req = read!(c, i16);
-        let fist_req = read!(c, i16);
-        let sword_req = read!(c, i16);
-        let knife_req = read!(c, i16);
-        let sp_weapon_req = read!(c, i16);
-        let arrow_req = read!(c, i16);
-        let ability_req = read!(c, i16);
-        let exp_req = read!(c, i16);
-        let item_exp = read!(c, i16);
-        let meterial_req = read!(c, i16);
-        let mut items = [0i16; 5];
-        let mut item_reqs = [0i16; 5];
-        for i in 0..items.len() {
-            items[i] = read!(c, i16);
-            item_reqs[i] = read!(c, i16);
-        }
-        Thing {
-            code,
-            name,
-            name2,
-            desp,
-            wugong,
-            animate_code,
-            user,
-            equip_type,
-            show,
-            typ,
-            unknown5,
-            unknown6,
-            unknown7,
-            add_life,
-            add_max_life,
-            add_detox,
-            add_vat,
-            change_mag,
-            add_mag,
-            add_max_mag,
-            add_attack,
-            add_agile,
-            add_def,
-            add_heal,
-            add_poison,
-            add_depoison,
-            add_anti_poi,
-            add_fist,
-            add_sword,
-            add_knife,
-            add_special_weapon,
-            add_arrow,
-            add_know,
-            add_rep,
-            add_attack_time,
-            add_attack_poi,
-            use_only,
-            magic_type,
-            magic_req,
-            att_req,
-            agile_req,
-            poi_req,
-            heal_req,
-            depoi_req,
-            fist_req,
-            sword_req,
-            knife_req,
-            sp_weapon_req,
-            arrow_req,
-            ability_req,
-            exp_req,
-            item_exp,
-            meterial_req,
-            items,
-            item_reqs,
-        }
+    pub fn name(&self, encoding: TextEncoding) -> String {
+        encoding::decode(encoding, &self.name)
+    }
+
+    pub fn name2(&self, encoding: TextEncoding) -> String {
+        encoding::decode(encoding, &self.name2)
+    }
+
+    pub fn desp(&self, encoding: TextEncoding) -> String {
+        encoding::decode(encoding, &self.desp)
+    }
+
+    // 0 剧情 1 装备 2 秘籍 3 药品 4 暗器
+    pub fn typ(&self) -> i16 {
+        self.typ
+    }
+
+    pub fn add_life(&self) -> i16 {
+        self.add_life
+    }
+
+    pub fn add_mag(&self) -> i16 {
+        self.add_mag
+    }
+
+    // below 0 cures the poison
+    pub fn add_detox(&self) -> i16 {
+        self.add_detox
+    }
+
+    pub fn add_attack(&self) -> i16 {
+        self.add_attack
+    }
+
+    pub fn add_agile(&self) -> i16 {
+        self.add_agile
+    }
+
+    pub fn add_def(&self) -> i16 {
+        self.add_def
+    }
+
+    pub fn add_anti_poi(&self) -> i16 {
+        self.add_anti_poi
+    }
+
+    pub fn add_attack_poi(&self) -> i16 {
+        self.add_attack_poi
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, JyRecord)]
+#[jy(size = 146)]
+pub struct Wugong {
+    // CC.Wugong_S["代号"]={0,0,2}
+    code: i16,
+    // CC.Wugong_S["名称"]={2,2,20}
+    #[jy(offset = 2)]
+    name: [u8; 20],
+    // CC.Wugong_S["未知1"]={22,0,2}
+    #[jy(offset = 22)]
+    unknown1: i16,
+    // CC.Wugong_S["未知2"]={24,0,2}
+    unknown2: i16,
+    // CC.Wugong_S["未知3"]={26,0,2}
+    unknown3: i16,
+    // CC.Wugong_S["未知4"]={28,0,2}
+    unknown4: i16,
+    // CC.Wugong_S["未知5"]={30,0,2}
+    unknown5: i16,
+    // CC.Wugong_S["出招音效"]={32,0,2}
+    sound: i16,
+    // CC.Wugong_S["武功类型"]={34,0,2}
+    typ: i16,
+    // CC.Wugong_S["武功动画&音效"]={36,0,2}
+    animation: i16,
+    // CC.Wugong_S["伤害类型"]={38,0,2}
+    hurt_type: i16,
+    // CC.Wugong_S["攻击范围"]={40,0,2}
+    range_type: i16,
+    // CC.Wugong_S["消耗内力点数"]={42,0,2}
+    mp_cost: i16,
+    // CC.Wugong_S["敌人中毒点数"]={44,0,2}
+    poison: i16,
+    // for i=1,10 do
+    // CC.Wugong_S["攻击力" .. i]={46+2*(i-1),0,2};
+    // CC.Wugong_S["移动范围" .. i]={66+2*(i-1),0,2};
+    // CC.Wugong_S["杀伤范围" .. i]={86+2*(i-1),0,2};
+    // CC.Wugong_S["加内力" .. i]={106+2*(i-1),0,2};
+    // CC.Wugong_S["杀内力" .. i]={126+2*(i-1),0,2};
+    // end
+    #[jy(offset = 46)]
+    attacks: [i16; WUGONG_LEVEL],
+    #[jy(offset = 66)]
+    moves: [i16; WUGONG_LEVEL],
+    #[jy(offset = 86)]
+    ranges: [i16; WUGONG_LEVEL],
+    #[jy(offset = 106)]
+    add_mp: [i16; WUGONG_LEVEL],
+    #[jy(offset = 126)]
+    kill_mp: [i16; WUGONG_LEVEL],
+}
+
+impl Wugong {
+    /// The table index of a 武功等级 (0 - 999), the original uses level/100 + 1.
+    pub fn level_of(skill_level: i16) -> usize {
+        (skill_level.max(0) as usize / 100).min(WUGONG_LEVEL - 1)
+    }
+
+    pub fn code(&self) -> i16 {
+        self.code
+    }
+
+    pub fn name(&self, encoding: TextEncoding) -> String {
+        encoding::decode(encoding, &self.name)
+    }
+
+    pub fn sound(&self) -> i16 {
+        self.sound
+    }
+
+    // 1 拳 2 剑 3 刀 4 特殊
+    pub fn typ(&self) -> i16 {
+        self.typ
+    }
+
+    pub fn animation(&self) -> i16 {
+        self.animation
+    }
+
+    // 0 杀生命 1 杀内力
+    pub fn hurt_type(&self) -> i16 {
+        self.hurt_type
+    }
+
+    // 0 点 1 线 2 十字 3 面
+    pub fn range_type(&self) -> i16 {
+        self.range_type
+    }
+
+    pub fn mp_cost(&self) -> i16 {
+        self.mp_cost
+    }
+
+    pub fn poison(&self) -> i16 {
+        self.poison
+    }
+
+    pub fn attack(&self, level: usize) -> i16 {
+        self.attacks[level]
+    }
+
+    pub fn move_range(&self, level: usize) -> i16 {
+        self.moves[level]
+    }
+
+    pub fn kill_range(&self, level: usize) -> i16 {
+        self.ranges[level]
+    }
+
+    pub fn add_mp(&self, level: usize) -> i16 {
+        self.add_mp[level]
+    }
+
+    pub fn kill_mp(&self, level: usize) -> i16 {
+        self.kill_mp[level]
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, JyRecord)]
+#[jy(size = 30)]
+pub struct Shop {
+    // for i=1,5 do
+    // CC.Shop_S["物品" .. i]={0+2*(i-1),0,2};
+    // CC.Shop_S["物品数量" .. i]={10+2*(i-1),0,2};
+    // CC.Shop_S["物品价格" .. i]={20+2*(i-1),0,2};
+    // end
+    #[jy(offset = 0)]
+    items: [i16; SHOP_ITEM],
+    #[jy(offset = 10)]
+    nums: [i16; SHOP_ITEM],
+    #[jy(offset = 20)]
+    prices: [i16; SHOP_ITEM],
+}
+
+impl Shop {
+    /// The goods still in stock as (slot, thing id, count, price).
+    pub fn goods(&self) -> impl Iterator<Item = (usize, usize, i16, i16)> + '_ {
+        (0..SHOP_ITEM)
+            .filter(|i| self.items[*i] >= 0 && self.nums[*i] > 0)
+            .map(|i| (i, self.items[i] as usize, self.nums[i], self.prices[i]))
     }
 
-    pub fn name(&self) -> String {
-        to_str(&self.name)
+    pub fn item(&self, slot: usize) -> i16 {
+        self.items[slot]
     }
 
-    pub fn name2(&self) -> String {
-        to_str(&self.name2)
+    pub fn num(&self, slot: usize) -> i16 {
+        self.nums[slot]
     }
 
-    pub fn desp(&self) -> String {
-        to_str(&self.desp)
+    pub fn price(&self, slot: usize) -> i16 {
+        self.prices[slot]
+    }
+
+    /// Takes one piece out of the stock of a slot, false if it is sold out.
+    pub fn sell(&mut self, slot: usize) -> bool {
+        if self.nums[slot] <= 0 {
+            return false;
+        }
+        self.nums[slot] -= 1;
+        true
     }
 }
 
-pub struct Wugong([u8; 146]);
+/// A battle of war.sta, `instruct_6` starts it by its index.
+#[derive(Debug, JyRecord)]
+#[jy(size = 186)]
+pub struct WarSta {
+    // CC.WarData_S["代号"]={0,0,2}
+    pub code: i16,
+    // CC.WarData_S["名称"]={2,2,10}
+    #[jy(offset = 2)]
+    pub name: [u8; 10],
+    // CC.WarData_S["地图"]={12,0,2}
+    #[jy(offset = 12)]
+    pub map: i16,
+    // CC.WarData_S["经验"]={14,0,2}
+    pub exp: i16,
+    // CC.WarData_S["音乐"]={16,0,2}
+    pub music: i16,
+    /*
+    for i=1,6 do
+    CC.WarData_S["手动选择参战人" .. i]={18+(i-1)*2,0,2};
+    CC.WarData_S["自动选择参战人" .. i]={30+(i-1)*2,0,2};
+    CC.WarData_S["我方X" .. i]={42+(i-1)*2,0,2};
+    CC.WarData_S["我方Y" .. i]={54+(i-1)*2,0,2};
+    end
+     */
+    #[jy(offset = 18)]
+    pub selectable: [i16; WAR_ALLY_NUM],
+    #[jy(offset = 30)]
+    pub auto_allies: [i16; WAR_ALLY_NUM],
+    #[jy(offset = 42)]
+    pub ally_x: [i16; WAR_ALLY_NUM],
+    #[jy(offset = 54)]
+    pub ally_y: [i16; WAR_ALLY_NUM],
+    /*
+    for i=1,20 do
+    CC.WarData_S["敌人" .. i]={66+(i-1)*2,0,2};
+    CC.WarData_S["敌方X" .. i]={106+(i-1)*2,0,2};
+    CC.WarData_S["敌方Y" .. i]={146+(i-1)*2,0,2};
+    end
+     */
+    #[jy(offset = 66)]
+    pub enemies: [i16; WAR_ENEMY_NUM],
+    #[jy(offset = 106)]
+    pub enemy_x: [i16; WAR_ENEMY_NUM],
+    #[jy(offset = 146)]
+    pub enemy_y: [i16; WAR_ENEMY_NUM],
+}
 
-pub struct Shop([u8; 30]);
+impl WarSta {
+    pub fn name(&self, encoding: TextEncoding) -> String {
+        encoding::decode(encoding, &self.name)
+    }
+}
 
 #[derive(PartialEq, Copy, Clone, Hash, Eq, Debug)]
 pub enum MoveDir {
@@ -942,6 +1260,25 @@ impl MoveDir {
         }
     }
 
+    // CC.Base_S["人方向"], same order as the walking pics: 0 up, 1 right, 2 left, 3 down
+    pub fn from_dir(dir: i16) -> Self {
+        match dir {
+            1 => MoveDir::Right,
+            2 => MoveDir::Left,
+            3 => MoveDir::Down,
+            _ => MoveDir::Up,
+        }
+    }
+
+    pub fn to_dir(&self) -> i16 {
+        match self {
+            MoveDir::Up => 0,
+            MoveDir::Right => 1,
+            MoveDir::Left => 2,
+            MoveDir::Down => 3,
+        }
+    }
+
     pub fn from(key: KeyCode) -> Option<Self> {
         match key {
             KeyCode::Up => Some(MoveDir::Up),
@@ -961,4 +1298,4 @@ pub struct SceneStatus {
     pub pos: PosXY,
     pub cur_pic: usize,
     pub is_new_game: bool,
-}
\ No newline at end of file
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use jy_derive::JyMenu;
+
+use crate::game::smap::ItemMenu;
+use crate::game::util::despawn_screen;
+use crate::game::{GameState, Menu};
+
+pub struct Plugin;
+
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(GameState::Smap).with_system(open_system_menu))
+            .add_system_set(SystemSet::on_update(GameState::Mmap).with_system(open_system_menu))
+            .add_system_set(SystemSet::on_enter(GameState::System).with_system(setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::System).with_system(keyboard_system_menu),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::System).with_system(despawn_screen::<SystemScreen>),
+            );
+    }
+}
+
+#[derive(Clone, JyMenu)]
+enum SystemOption {
+    Items,
+    Save,
+}
+
+const OPTION_NAMES: [&str; 2] = ["物品", "存档"];
+
+#[derive(Component)]
+pub struct SystemScreen;
+
+#[derive(Component)]
+struct SystemRow(usize);
+
+// the Esc of the item list closes the list only
+fn open_system_menu(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+    item_menu: Option<Res<ItemMenu>>,
+) {
+    if item_menu.is_none() && keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        commands.insert_resource(SystemOption::Items);
+        state.push(GameState::System).unwrap();
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/simsun.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(20.),
+                    top: Val::Px(20.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(10.)),
+                ..Default::default()
+            },
+            color: Color::rgba(0., 0., 0., 0.8).into(),
+            ..Default::default()
+        })
+        .insert(SystemScreen)
+        .with_children(|p| {
+            for (idx, name) in OPTION_NAMES.iter().enumerate() {
+                p.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(5.)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        *name,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 32.0,
+                            color: if idx == 0 { Color::WHITE } else { Color::GRAY },
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(SystemRow(idx));
+            }
+        });
+}
+
+// Return opens the picked screen in place of the menu, Esc goes back to the map
+fn keyboard_system_menu(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+    options: Res<SystemOption>,
+    mut query: Query<(&SystemRow, &mut Text)>,
+) {
+    for (row, mut text) in query.iter_mut() {
+        text.sections[0].style.color = if row.0 == options.to_idx() {
+            Color::WHITE
+        } else {
+            Color::GRAY
+        };
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        commands.insert_resource(options.up());
+        keyboard_input.clear();
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        commands.insert_resource(options.down());
+        keyboard_input.clear();
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        keyboard_input.clear();
+        let next = match *options {
+            SystemOption::Items => GameState::Inventory,
+            SystemOption::Save => GameState::Save,
+        };
+        state.set(next).unwrap();
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.clear();
+        state.pop().unwrap();
+    }
+}
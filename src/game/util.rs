@@ -23,9 +23,13 @@ impl FromWorld for ImageCache {
     fn from_world(world: &mut World) -> Self {
         let smap_text_map = world.get_resource::<SMapTexture>();
         let mmap_text_map = world.get_resource::<MMapTexture>();
+        let head_text_map = world.get_resource::<HeadTexture>();
+        let wmap_text_map = world.get_resource::<WMapTexture>();
         let palette = unsafe { std::mem::transmute(world.get_resource::<Palette>().unwrap()) };
         let smap :&'static SMapTexture = unsafe { std::mem::transmute(smap_text_map.unwrap()) };
         let mmap :&'static MMapTexture = unsafe { std::mem::transmute(mmap_text_map.unwrap()) };
+        let head :&'static HeadTexture = unsafe { std::mem::transmute(head_text_map.unwrap()) };
+        let wmap :&'static WMapTexture = unsafe { std::mem::transmute(wmap_text_map.unwrap()) };
 
         let assets = unsafe {
             std::mem::transmute(world.get_resource_mut::<Assets<Image>>().unwrap().as_mut())
@@ -33,6 +37,8 @@ impl FromWorld for ImageCache {
         let mut textures = HashMap::default();
         textures.insert(MapType::Mmap, &mmap.0);
         textures.insert(MapType::Smap, &smap.0);
+        textures.insert(MapType::Head, &head.0);
+        textures.insert(MapType::Wmap, &wmap.0);
         Self {
             cached: HashMap::default(),
             textures,
@@ -50,13 +56,19 @@ impl ImageCache {
     ) -> Option<(Handle<Image>, TextureMeta, Option<&'a Image>)> {
         if let Some((h, meta)) = self.cached.get(&(mtype, id)) {
             Some((h.clone(), *meta, self.assets.get(h)))
-        } else if let Some((image, meta)) = self.textures[&mtype].get_image(id, &self.palette.0) {
-            let handle = self.assets.add(image);
-            self.cached.insert((mtype, id), (handle.clone(), meta.clone()));
-            Some((handle.clone(), meta, self.assets.get(handle)))
         } else {
-            println!("no image here, the asset id: {} is wrong", id);
-            None
+            match self.textures[&mtype].get_image(id, &self.palette.0) {
+                Ok(Some((image, meta))) => {
+                    let handle = self.assets.add(image);
+                    self.cached.insert((mtype, id), (handle.clone(), meta));
+                    Some((handle.clone(), meta, self.assets.get(handle)))
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    warn!("no image here, the asset id: {} is wrong: {}", id, e);
+                    None
+                }
+            }
         }
     }
 }
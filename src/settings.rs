@@ -7,10 +7,41 @@ use serde::Deserialize;
 use bevy::log::Level;
 use bevy::prelude::{FromWorld, World};
 
+use crate::game::battle::ai::Difficulty;
+use crate::game::encoding::TextEncoding;
+use crate::game::script::ScriptSource;
+
 #[derive(Debug, Deserialize)]
 pub struct Settings {
     // ["off", "error", "warn", "info", "debug", "trace"];
     log: String,
+    // ["big5", "gbk", "utf-8"], the text encoding of the data files
+    #[serde(default = "default_encoding")]
+    encoding: String,
+    // ["lua", "kdef"], lua runs kdef.grp for the events without a converted script
+    #[serde(default = "default_script")]
+    script: String,
+    // shows the script errors on screen
+    #[serde(default)]
+    dev: bool,
+    // the frames of every effect of eft.grp in a row, in place of `anim::EFFECT_FRAMES`
+    #[serde(default)]
+    effect_frames: Option<Vec<usize>>,
+    // ["easy", "normal", "hard"], how the enemies fight
+    #[serde(default = "default_difficulty")]
+    difficulty: String,
+}
+
+fn default_encoding() -> String {
+    "big5".into()
+}
+
+fn default_script() -> String {
+    "lua".into()
+}
+
+fn default_difficulty() -> String {
+    "normal".into()
 }
 
 impl FromWorld for Settings {
@@ -18,6 +49,11 @@ impl FromWorld for Settings {
         #[cfg(target_arch = "wasm32")]
         return Settings {
             log: "error".into(),
+            encoding: default_encoding(),
+            script: default_script(),
+            dev: false,
+            effect_frames: None,
+            difficulty: default_difficulty(),
         };
         #[cfg(not(target_arch = "wasm32"))]
         match Settings::load() {
@@ -26,6 +62,11 @@ impl FromWorld for Settings {
                 println!("failed to load from config: {:?}\n, load default", e);
                 Settings {
                     log: "error".into(),
+                    encoding: default_encoding(),
+                    script: default_script(),
+                    dev: false,
+                    effect_frames: None,
+                    difficulty: default_difficulty(),
                 }
             }
         }
@@ -37,6 +78,26 @@ impl Settings {
         Level::from_str(&self.log).unwrap_or(Level::ERROR)
     }
 
+    pub fn encoding(&self) -> TextEncoding {
+        TextEncoding::from_str(&self.encoding).unwrap_or(TextEncoding::Big5)
+    }
+
+    pub fn script_source(&self) -> ScriptSource {
+        ScriptSource::from_str(&self.script).unwrap_or(ScriptSource::Lua)
+    }
+
+    pub fn dev(&self) -> bool {
+        self.dev
+    }
+
+    pub fn effect_frames(&self) -> Option<&[usize]> {
+        self.effect_frames.as_deref()
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        Difficulty::from_str(&self.difficulty).unwrap_or(Difficulty::Normal)
+    }
+
     pub fn load() -> Result<Self, ConfigError> {
         let run_mode = env::var("RUN_MODE").unwrap_or_else(|_| "dev".into());
 
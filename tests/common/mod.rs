@@ -0,0 +1,224 @@
+//! A headless world that runs the event scripts through the real `script::Plugin`.
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, HashSet};
+use std::time::{Duration, Instant};
+
+use bevy::asset::AssetPlugin;
+use bevy::prelude::*;
+
+use jy::game::encoding::{self, TextEncoding};
+use jy::game::script::{self, EventScript, JyEvent, ScriptRuntime, ScriptSource};
+use jy::game::structs::{
+    Backpack, DData, Kdef, SData, SceneStatus, Talks, Thing, SCENE_EVENT_BYTES, SCENE_MAP_BYTES,
+};
+use jy::game::{GameState, GrpAsset, Record};
+
+// enough for every thing id the original scripts hand out
+const THING_NUM: usize = 200;
+const SCENE_NUM: usize = 2;
+// an asset load or a script that never waits should not take longer
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct ScriptHarness {
+    pub app: App,
+    talks: BTreeMap<usize, String>,
+    kdef: BTreeMap<usize, Vec<i16>>,
+    // every text shown so far, in order
+    texts: Vec<String>,
+    seen: HashSet<Entity>,
+}
+
+impl ScriptHarness {
+    /// Zeroed scenes, nameless things, an empty backpack and no talks.
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Image>()
+            .add_asset::<TextureAtlas>()
+            .add_state(GameState::Smap)
+            .add_event::<JyEvent>()
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(
+                DData::new(grp(vec![0; SCENE_NUM * SCENE_EVENT_BYTES]), SCENE_NUM).unwrap(),
+            )
+            .insert_resource(
+                SData::new(grp(vec![0; SCENE_NUM * SCENE_MAP_BYTES]), SCENE_NUM).unwrap(),
+            )
+            .insert_resource(Backpack { items: vec![] })
+            .insert_resource(
+                (0..THING_NUM)
+                    .map(|_| Thing::from_bytes(&[0; Thing::SIZE]).unwrap())
+                    .collect::<Vec<_>>(),
+            )
+            .insert_resource(SceneStatus::default())
+            .insert_resource(Talks(grp(vec![])))
+            .insert_resource(TextEncoding::Big5)
+            .add_plugin(script::Plugin);
+        // the states start on the first update
+        app.update();
+
+        ScriptHarness {
+            app,
+            talks: BTreeMap::new(),
+            kdef: BTreeMap::new(),
+            texts: vec![],
+            seen: HashSet::new(),
+        }
+    }
+
+    pub fn set_thing_name(&mut self, id: usize, name: &str) {
+        let offset = Thing::offset_of("name").unwrap();
+        let mut things = self.app.world.get_resource_mut::<Vec<Thing>>().unwrap();
+        let mut data = things[id].to_bytes();
+        encoding::encode_into(TextEncoding::Big5, name, &mut data[offset..offset + 20]);
+        things[id] = Thing::from_bytes(&data).unwrap();
+    }
+
+    /// Adds a talk.grp entry, `*` is a line break as in the original.
+    pub fn set_talk(&mut self, id: usize, text: &str) {
+        self.talks.insert(id, text.into());
+        let mut idx = vec![0];
+        let mut data = vec![];
+        for i in 0..=*self.talks.keys().last().unwrap() {
+            if let Some(text) = self.talks.get(&i) {
+                let mut bytes = vec![0; text.len() * 2 + 1];
+                encoding::encode_into(TextEncoding::Big5, text, &mut bytes);
+                let len = bytes.iter().position(|v| *v == 0).unwrap() + 1;
+                data.extend(bytes[..len].iter().map(|v| v ^ 0xFF));
+            }
+            idx.push(data.len());
+        }
+        self.app
+            .world
+            .insert_resource(Talks(GrpAsset { idx, data }));
+    }
+
+    /// Adds a kdef.grp event, the lua scripts fall back to it when there is no `oldevent_N.lua`.
+    pub fn set_kdef(&mut self, event_id: usize, code: &[i16]) {
+        self.kdef.insert(event_id, code.to_vec());
+        let mut idx = vec![0];
+        let mut data = vec![];
+        for i in 0..=*self.kdef.keys().last().unwrap() {
+            for word in self.kdef.get(&i).into_iter().flatten() {
+                data.extend(word.to_le_bytes());
+            }
+            idx.push(data.len());
+        }
+        self.app.world.insert_resource(Kdef(GrpAsset { idx, data }));
+    }
+
+    pub fn set_source(&mut self, source: ScriptSource) {
+        self.app
+            .world
+            .get_resource_mut::<ScriptRuntime>()
+            .unwrap()
+            .source = source;
+    }
+
+    pub fn scene_status(&mut self) -> Mut<'_, SceneStatus> {
+        self.app.world.get_resource_mut::<SceneStatus>().unwrap()
+    }
+
+    pub fn d_data(&self) -> &DData {
+        self.app.world.get_resource::<DData>().unwrap()
+    }
+
+    pub fn d_data_mut(&mut self) -> Mut<'_, DData> {
+        self.app.world.get_resource_mut::<DData>().unwrap()
+    }
+
+    pub fn backpack(&self) -> &Backpack {
+        self.app.world.get_resource::<Backpack>().unwrap()
+    }
+
+    /// Every text shown so far, dialogs and questions alike.
+    pub fn texts(&self) -> &[String] {
+        &self.texts
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.app
+            .world
+            .get_resource::<State<GameState>>()
+            .unwrap()
+            .current()
+            == &GameState::Interaction
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.app
+            .world
+            .get_resource::<EventScript>()
+            .map_or(false, |v| v.wait_input)
+    }
+
+    /// Starts `assets/script/oldevent_N.lua` as `smap` does and runs it until it waits for a key or ends.
+    pub fn run_event(&mut self, event_id: i16) {
+        self.start_event(event_id);
+        self.run();
+    }
+
+    /// Starts the event for one update only.
+    pub fn start_event(&mut self, event_id: i16) {
+        let world = &mut self.app.world;
+        world.resource_scope(|world, mut runtime: Mut<ScriptRuntime>| {
+            let asset_server = world.get_resource::<AssetServer>().unwrap().clone();
+            let mut state = world.get_resource_mut::<State<GameState>>().unwrap();
+            script::execute_n(&mut state, &mut runtime, &asset_server, event_id);
+        });
+        self.update();
+    }
+
+    /// Updates until the script waits for a key or the interaction is over.
+    pub fn run(&mut self) {
+        let start = Instant::now();
+        while self.is_running() && !self.is_waiting() {
+            assert!(
+                start.elapsed() < TIMEOUT,
+                "the script neither ends nor waits"
+            );
+            self.update();
+        }
+    }
+
+    /// Presses and releases a key for one frame, then runs on.
+    pub fn press(&mut self, key: KeyCode) {
+        self.input().press(key);
+        self.update();
+        let mut input = self.input();
+        input.release(key);
+        input.clear();
+        self.run();
+    }
+
+    /// Confirms every dialog with Return until the script ends.
+    pub fn finish(&mut self) {
+        while self.is_running() {
+            self.press(KeyCode::Return);
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.app.update();
+        let mut query = self.app.world.query::<(Entity, &Text)>();
+        for (entity, text) in query.iter(&self.app.world) {
+            if self.seen.insert(entity) {
+                self.texts
+                    .push(text.sections.iter().map(|v| v.value.as_str()).collect());
+            }
+        }
+    }
+
+    fn input(&mut self) -> Mut<'_, Input<KeyCode>> {
+        self.app.world.get_resource_mut::<Input<KeyCode>>().unwrap()
+    }
+}
+
+fn grp(data: Vec<u8>) -> GrpAsset {
+    GrpAsset {
+        idx: vec![0, data.len()],
+        data,
+    }
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use bevy::prelude::KeyCode;
+
+use jy::game::script::ScriptSource;
+
+use common::ScriptHarness;
+
+#[test]
+fn test_new_game_event() {
+    let mut h = ScriptHarness::new();
+    h.set_talk(2520, "啊！頭好痛呀！*是不是最近Ｋ書Ｋ太凶了");
+    h.run_event(691);
+    assert!(h.is_waiting());
+    assert_eq!(h.texts(), ["啊！頭好痛呀！\n是不是最近Ｋ書Ｋ太凶了"]);
+
+    h.press(KeyCode::Return);
+    assert!(!h.is_running());
+    let d = h.d_data();
+    assert_eq!(d.get_d(0, 0, 0).unwrap(), 0);
+    assert_eq!(d.get_d(0, 0, 7).unwrap(), -1);
+    assert_eq!(d.get_d(0, 1, 2).unwrap(), 692);
+    assert_eq!(d.get_d(0, 1, 3).unwrap(), -1);
+    assert_eq!(d.get_d(0, 1, 5).unwrap(), 0);
+}
+
+#[test]
+fn test_item_event() {
+    let mut h = ScriptHarness::new();
+    h.set_thing_name(174, "銀兩");
+    h.scene_status().cur_d = (3, 10, 12);
+    h.run_event(693);
+    assert_eq!(h.texts(), ["得到物品:銀兩 100"]);
+
+    h.finish();
+    assert_eq!(h.backpack().items, vec![(174, 100)]);
+    let d = h.d_data();
+    assert_eq!(d.get_d(0, 3, 4).unwrap(), -1);
+    assert_eq!(d.get_d(0, 3, 5).unwrap(), 3500);
+    assert_eq!(d.get_d(0, 3, 7).unwrap(), 3500);
+}
+
+// if instruct_5() then instruct_3(-2, -2, -2, -2, 1, -2, ...) end, instruct_0()
+const KDEF_ASK: [i16; 19] = [
+    5, 0, 14, 3, -2, -2, -2, -2, 1, -2, -2, -2, -2, -2, -2, -2, -2, 0, -1,
+];
+
+#[test]
+fn test_kdef_event() {
+    for (key, d2) in [(KeyCode::Y, 1), (KeyCode::N, 0)] {
+        let mut h = ScriptHarness::new();
+        h.set_source(ScriptSource::Kdef);
+        h.set_kdef(693, &KDEF_ASK);
+        h.scene_status().cur_d = (3, 10, 12);
+        h.run_event(693);
+        assert!(h.is_waiting());
+        assert_eq!(h.texts(), ["是否与之过招(Y/N)?", "是", "否"]);
+
+        h.press(key);
+        assert!(!h.is_running());
+        assert_eq!(h.d_data().get_d(0, 3, 2).unwrap(), d2);
+    }
+}
+
+#[test]
+fn test_choice_menu() {
+    // Down to 否 and back, Esc cancels as 否
+    for (keys, d2) in [
+        (&[KeyCode::Down, KeyCode::Return][..], 0),
+        (&[KeyCode::Down, KeyCode::Up, KeyCode::Return][..], 1),
+        (&[KeyCode::Escape][..], 0),
+    ] {
+        let mut h = ScriptHarness::new();
+        h.set_source(ScriptSource::Kdef);
+        h.set_kdef(693, &KDEF_ASK);
+        h.scene_status().cur_d = (3, 10, 12);
+        h.run_event(693);
+        for key in keys {
+            assert!(h.is_waiting());
+            h.press(*key);
+        }
+        assert!(!h.is_running());
+        assert_eq!(h.d_data().get_d(0, 3, 2).unwrap(), d2);
+    }
+}
+
+#[test]
+fn test_kdef_fallback() {
+    let mut h = ScriptHarness::new();
+    h.set_kdef(1, &KDEF_ASK);
+    h.run_event(1);
+    assert!(h.is_waiting());
+    h.press(KeyCode::Y);
+    assert!(!h.is_running());
+    assert_eq!(h.d_data().get_d(0, 0, 2).unwrap(), 1);
+}
+
+#[test]
+fn test_animation() {
+    let mut h = ScriptHarness::new();
+    // instruct_27(3, 100, 104), instruct_27(-1, 6890, 6894)
+    h.set_kdef(1, &[27, 3, 100, 104, 27, -1, 6890, 6894, -1]);
+    for i in 5..=7 {
+        h.d_data_mut().set(0, 3, i, 50).unwrap();
+    }
+
+    h.start_event(1);
+    let mut pics = vec![];
+    let mut player = vec![];
+    while h.is_running() {
+        assert!(!h.is_waiting());
+        pics.push(h.d_data().get_d(0, 3, 5).unwrap());
+        player.push(h.scene_status().cur_pic);
+        h.update();
+    }
+    pics.dedup();
+    player.dedup();
+    // the event is put back the way it was
+    assert_eq!(pics, [50, 100, 102, 104, 50]);
+    assert_eq!(h.d_data().get_d(0, 3, 7).unwrap(), 50);
+    // the player stays on the last picture
+    assert_eq!(player, [0, 3445, 3446, 3447]);
+}
+
+#[test]
+fn test_missing_battle() {
+    let mut h = ScriptHarness::new();
+    h.set_source(ScriptSource::Kdef);
+    // if instruct_6(3, 0, 3, 0) then instruct_2(174, 1) end
+    h.set_kdef(1, &[6, 3, 0, 3, 0, 2, 174, 1, -1]);
+    h.run_event(1);
+    // no war.sta, the battle is lost right away
+    assert!(!h.is_running());
+    assert!(h.backpack().items.is_empty());
+}
@@ -7,8 +7,12 @@ use byteorder::{LittleEndian, ReadBytesExt};
 
 use bevy::log::Level;
 use bevy::prelude::{FromWorld, World};
-use jy::game::structs::Person;
-use jy::game::{structs, GrpAsset};
+use jy::game::encoding::TextEncoding;
+use jy::game::structs::{
+    Backpack, Base, DData, Person, Scene, Shop, Talks, Thing, Trigger, WarSta, Wugong,
+    MY_THING_NUM, SCENE_EVENT_BYTES,
+};
+use jy::game::{structs, GrpAsset, Record};
 use jy::prelude::Settings;
 
 #[test]
@@ -21,6 +25,77 @@ fn test_settings() {
 #[test]
 fn test_structs() {
     assert_eq!(mem::size_of::<Person>(), 202);
+    assert_eq!(mem::size_of::<Wugong>(), 146);
+    assert_eq!(mem::size_of::<Shop>(), 30);
+}
+
+#[test]
+fn test_talks() {
+    let mut data = "金庸*群俠傳".as_bytes().to_vec();
+    data.push(0);
+    let talks = Talks(GrpAsset {
+        idx: vec![0, 2, 2 + data.len()],
+        data: [b"hi".to_vec(), data.iter().map(|v| v ^ 0xFF).collect()].concat(),
+    });
+    assert_eq!(talks.get(1, TextEncoding::Utf8).unwrap(), "金庸\n群俠傳");
+    assert!(talks.get(2, TextEncoding::Utf8).is_err());
+}
+
+#[test]
+fn test_backpack() {
+    let mut backpack = Backpack::default();
+    for thing in 0..MY_THING_NUM as i16 + 10 {
+        backpack.add(thing, 1);
+    }
+    // no room left in Base::items for the last ones
+    assert_eq!(backpack.items.len(), MY_THING_NUM);
+    assert_eq!(backpack.items.last(), Some(&(MY_THING_NUM as i16 - 1, 1)));
+    backpack.add(0, 2);
+    assert_eq!(backpack.items[0], (0, 3));
+    backpack.add(0, -3);
+    assert_eq!(backpack.items.len(), MY_THING_NUM - 1);
+    backpack.add(500, 1);
+    assert_eq!(backpack.items.last(), Some(&(500, 1)));
+}
+
+#[test]
+fn test_triggers() {
+    let data = vec![0; SCENE_EVENT_BYTES];
+    let mut d = DData::new(GrpAsset { idx: vec![0, data.len()], data }, 1).unwrap();
+    d.set(0, 5, 2, 100).unwrap();
+    d.set(0, 5, 4, 101).unwrap();
+    d.set(0, 5, 3, -1).unwrap();
+    assert_eq!(d.event(0, 5, Trigger::Talk), Some(100));
+    assert_eq!(d.event(0, 5, Trigger::Item), None);
+    assert_eq!(d.event(0, 5, Trigger::Step), Some(101));
+    assert_eq!(d.event(1, 5, Trigger::Step), None);
+}
+
+fn round_trip<T: Record>() {
+    let data = (0..T::SIZE).map(|v| v as u8).collect::<Vec<_>>();
+    assert_eq!(T::from_bytes(&data).unwrap().to_bytes(), data);
+    assert!(T::from_bytes(&data[1..]).is_err());
+}
+
+#[test]
+fn test_records() {
+    assert_eq!(Base::SIZE, 836);
+    assert_eq!(Person::SIZE, 202);
+    assert_eq!(Thing::SIZE, 260);
+    assert_eq!(Scene::SIZE, 62);
+    assert_eq!(Wugong::SIZE, 146);
+    assert_eq!(Shop::SIZE, 30);
+    assert_eq!(WarSta::SIZE, 186);
+    assert_eq!(WarSta::offset_of("enemy_y"), Some(146));
+    assert_eq!(Person::offset_of("skills"), Some(146));
+    assert_eq!(Thing::offset_of("item_reqs"), Some(250));
+    round_trip::<Base>();
+    round_trip::<Person>();
+    round_trip::<Thing>();
+    round_trip::<Scene>();
+    round_trip::<Wugong>();
+    round_trip::<Shop>();
+    round_trip::<WarSta>();
 }
 
 #[test]